@@ -0,0 +1,53 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{AccountId, EpochHeight};
+
+use crate::{Registry, ValidatorInfo};
+
+/// Change of the Registry. Changes are proposed to the council and queued by the timelock.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum RegistryAction {
+    AddValidator { validator: AccountId, info: ValidatorInfo },
+    DelValidator { validator: AccountId },
+    UpdateValidator { validator: AccountId, info: ValidatorInfo },
+    SetWeights { weights: Vec<(AccountId, u32)> },
+    /// Changes the number of confirmations a council proposal needs.
+    ChangeThreshold { threshold: u32 },
+    /// Replaces the members of the council.
+    ChangeMembers { members: Vec<AccountId> },
+    /// Hands the Registry over to a council, see `enable_council`.
+    EnableCouncil { members: Vec<AccountId>, threshold: u32, proposal_period: EpochHeight },
+    SetTimelock { delay: EpochHeight, guardian: AccountId },
+    /// Approves the code with the sha256 `code_hash`, see `deploy_upgrade`.
+    Upgrade { code_hash: Base64VecU8 },
+}
+
+impl Registry {
+    pub(crate) fn execute_action(&mut self, action: RegistryAction) {
+        match action {
+            RegistryAction::AddValidator { validator, info } => {
+                self.internal_add_validator(validator, info)
+            }
+            RegistryAction::DelValidator { validator } => self.internal_del_validator(validator),
+            RegistryAction::UpdateValidator { validator, info } => {
+                self.internal_update_validator(validator, info)
+            }
+            RegistryAction::SetWeights { weights } => self.internal_set_weights(weights),
+            RegistryAction::ChangeThreshold { threshold } => {
+                self.internal_change_threshold(threshold)
+            }
+            RegistryAction::ChangeMembers { members } => self.internal_change_members(members),
+            RegistryAction::EnableCouncil {
+                members,
+                threshold,
+                proposal_period,
+            } => self.internal_enable_council(members, threshold, proposal_period),
+            RegistryAction::SetTimelock { delay, guardian } => {
+                self.internal_set_timelock(delay, guardian)
+            }
+            RegistryAction::Upgrade { code_hash } => self.internal_approve_upgrade(code_hash.0),
+        }
+    }
+}
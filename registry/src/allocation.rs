@@ -0,0 +1,74 @@
+use near_sdk::json_types::U128;
+use near_sdk::{near_bindgen, AccountId, Balance};
+
+use crate::util::mul_div_rem;
+use crate::weights::split;
+use crate::{Registry, RegistryError, BASIS_POINTS};
+
+#[near_bindgen]
+impl Registry {
+    /// Splits `amount` across active validators by their weights. Yocto left by rounding
+    /// goes to the largest remainders, see `split`.
+    ///
+    /// With `current_stake`, the targets are the weights of the stake after the deposit, and
+    /// validators below their target are filled first, in proportion to how far below they
+    /// are. What is left once every target is met is split by the weights. Stake of
+    /// validators that are not active is ignored.
+    pub fn compute_allocation(
+        &self,
+        amount: U128,
+        current_stake: Option<Vec<(AccountId, U128)>>,
+    ) -> Vec<(AccountId, U128)> {
+        let amount: Balance = amount.into();
+        let validators = self.get_normalized_weights();
+        let weights: Vec<Balance> = validators.iter().map(|(_, weight)| (*weight).into()).collect();
+        if weights.iter().all(|weight| *weight == 0) {
+            RegistryError::NoActiveWeights.panic();
+        }
+
+        let current_stake = current_stake.unwrap_or_default();
+        let mut stakes: Vec<Balance> = vec![0; validators.len()];
+        for (index, (validator, stake)) in current_stake.iter().enumerate() {
+            if current_stake[..index].iter().any(|(account_id, _)| account_id == validator) {
+                RegistryError::DuplicateValidator(validator.clone()).panic();
+            }
+            if let Some(position) = validators.iter().position(|(v, _)| v == validator) {
+                stakes[position] = stake.0;
+            }
+        }
+
+        let total = checked_sum(stakes.iter().chain(std::iter::once(&amount)));
+        let deficits: Vec<Balance> = weights
+            .iter()
+            .zip(stakes.iter())
+            .map(|(weight, stake)| {
+                let (target, _) = mul_div_rem(total, *weight, BASIS_POINTS.into());
+                target.saturating_sub(*stake)
+            })
+            .collect();
+        let deficit_sum = checked_sum(deficits.iter());
+
+        let parts = if deficit_sum >= amount {
+            split(amount, &deficits)
+        } else {
+            split(amount - deficit_sum, &weights)
+                .into_iter()
+                .zip(deficits)
+                .map(|(part, deficit)| part + deficit)
+                .collect()
+        };
+        validators
+            .into_iter()
+            .zip(parts)
+            .map(|((validator, _), part)| (validator, part.into()))
+            .collect()
+    }
+}
+
+/// Sums `values`, panicking on overflow. Stakes are given by the caller and can be anything.
+fn checked_sum<'a>(values: impl Iterator<Item = &'a Balance>) -> Balance {
+    values.fold(0, |sum: Balance, value| {
+        sum.checked_add(*value)
+            .unwrap_or_else(|| RegistryError::AllocationOverflow.panic())
+    })
+}
@@ -0,0 +1,208 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId, EpochHeight};
+
+use crate::{Registry, RegistryAction, RegistryError};
+
+/// M-of-N council that governs the Registry instead of the Governance account.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Council {
+    pub members: Vec<AccountId>,
+    /// Confirmations a proposal needs to execute.
+    pub threshold: u32,
+    /// Number of epochs a proposal stays open.
+    pub proposal_period: EpochHeight,
+}
+
+/// Pending change of the Registry submitted by a council member.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Proposal {
+    pub proposer: AccountId,
+    pub action: RegistryAction,
+    /// Members that confirmed the proposal, the proposer included.
+    pub confirmations: Vec<AccountId>,
+    /// Epoch height from which the proposal can no longer be confirmed.
+    pub expires_at: EpochHeight,
+}
+
+impl Proposal {
+    pub fn is_expired(&self) -> bool {
+        env::epoch_height() >= self.expires_at
+    }
+}
+
+#[near_bindgen]
+impl Registry {
+    /// Hands the Registry over to a council of `members`, `threshold` of which must confirm
+    /// a proposal. Only Governance can enable the council, and it can't change the Registry
+    /// directly afterwards.
+    pub fn enable_council(
+        &mut self,
+        members: Vec<AccountId>,
+        threshold: u32,
+        proposal_period: EpochHeight,
+    ) {
+        self.assert_governance();
+        self.dispatch_action(RegistryAction::EnableCouncil {
+            members,
+            threshold,
+            proposal_period,
+        });
+    }
+
+    /// Submits `action` and confirms it for the caller. Only council members can propose.
+    /// Returns the id of the proposal, which is executed right away if the threshold is one.
+    pub fn propose(&mut self, action: RegistryAction) -> u64 {
+        let council = self.assert_council_member();
+        let proposer = env::predecessor_account_id();
+        let proposal_id = self.next_proposal_id;
+        self.next_proposal_id += 1;
+        let proposal = Proposal {
+            proposer: proposer.clone(),
+            action,
+            confirmations: vec![proposer],
+            expires_at: env::epoch_height() + council.proposal_period,
+        };
+        self.proposals.insert(&proposal_id, &proposal);
+        self.execute_if_confirmed(proposal_id, proposal);
+        proposal_id
+    }
+
+    /// Confirms a proposal for the caller. The proposal is executed, or queued if there is a
+    /// timelock, and removed as soon as the confirmations of current members reach the
+    /// threshold. Returns whether it was.
+    pub fn confirm(&mut self, proposal_id: u64) -> bool {
+        self.assert_council_member();
+        let member = env::predecessor_account_id();
+        let mut proposal = self
+            .proposals
+            .get(&proposal_id)
+            .unwrap_or_else(|| RegistryError::ProposalNotFound(proposal_id).panic());
+        if proposal.is_expired() {
+            RegistryError::ProposalExpired(proposal_id).panic();
+        }
+        if proposal.confirmations.contains(&member) {
+            RegistryError::AlreadyConfirmed(proposal_id).panic();
+        }
+        proposal.confirmations.push(member);
+        self.proposals.insert(&proposal_id, &proposal);
+        self.execute_if_confirmed(proposal_id, proposal)
+    }
+
+    /// Removes the given proposals that have expired. Anyone can call it.
+    /// Returns the number of removed proposals.
+    pub fn remove_expired_proposals(&mut self, proposal_ids: Vec<u64>) -> u32 {
+        let mut removed = 0;
+        for proposal_id in proposal_ids.iter() {
+            if let Some(proposal) = self.proposals.get(proposal_id) {
+                if proposal.is_expired() {
+                    self.proposals.remove(proposal_id);
+                    removed += 1;
+                }
+            }
+        }
+        removed
+    }
+
+    pub fn get_council(&self) -> Option<Council> {
+        self.council.clone()
+    }
+
+    pub fn get_proposal(&self, proposal_id: u64) -> Option<Proposal> {
+        self.proposals.get(&proposal_id)
+    }
+
+    /// Returns up to `limit` open proposals with their ids, starting at `from_index`.
+    pub fn get_proposals(&self, from_index: u64, limit: u64) -> Vec<(u64, Proposal)> {
+        let proposal_ids = self.proposals.keys_as_vector();
+        let proposals = self.proposals.values_as_vector();
+        let end = std::cmp::min(from_index.saturating_add(limit), proposal_ids.len());
+        (from_index..end)
+            .map(|index| (proposal_ids.get(index).unwrap(), proposals.get(index).unwrap()))
+            .collect()
+    }
+}
+
+impl Registry {
+    fn assert_council_member(&self) -> Council {
+        let council = self
+            .council
+            .clone()
+            .unwrap_or_else(|| RegistryError::CouncilNotEnabled.panic());
+        if !council.members.contains(&env::predecessor_account_id()) {
+            RegistryError::NotCouncilMember.panic();
+        }
+        council
+    }
+
+    fn execute_if_confirmed(&mut self, proposal_id: u64, proposal: Proposal) -> bool {
+        let council = self.council.as_ref().unwrap();
+        // Confirmations of removed members don't count.
+        let confirmations = proposal
+            .confirmations
+            .iter()
+            .filter(|member| council.members.contains(member))
+            .count();
+        if (confirmations as u32) < council.threshold {
+            return false;
+        }
+        self.proposals.remove(&proposal_id);
+        self.dispatch_action(proposal.action);
+        true
+    }
+
+    pub(crate) fn internal_enable_council(
+        &mut self,
+        members: Vec<AccountId>,
+        threshold: u32,
+        proposal_period: EpochHeight,
+    ) {
+        if self.council.is_some() {
+            RegistryError::GovernedByCouncil.panic();
+        }
+        if proposal_period == 0 {
+            RegistryError::InvalidProposalPeriod.panic();
+        }
+        assert_members(&members, threshold);
+        self.council = Some(Council {
+            members,
+            threshold,
+            proposal_period,
+        });
+    }
+
+    pub(crate) fn internal_change_threshold(&mut self, threshold: u32) {
+        let council = self
+            .council
+            .as_mut()
+            .unwrap_or_else(|| RegistryError::CouncilNotEnabled.panic());
+        assert_members(&council.members, threshold);
+        council.threshold = threshold;
+    }
+
+    pub(crate) fn internal_change_members(&mut self, members: Vec<AccountId>) {
+        let council = self
+            .council
+            .as_mut()
+            .unwrap_or_else(|| RegistryError::CouncilNotEnabled.panic());
+        assert_members(&members, council.threshold);
+        council.members = members;
+    }
+}
+
+/// Panics unless `members` are distinct valid accounts and `threshold` of them can confirm.
+fn assert_members(members: &[AccountId], threshold: u32) {
+    for (index, member) in members.iter().enumerate() {
+        if !env::is_valid_account_id(member.as_bytes()) {
+            RegistryError::InvalidMemberAccountId.panic();
+        }
+        if members[..index].contains(member) {
+            RegistryError::DuplicateMember(member.clone()).panic();
+        }
+    }
+    if threshold == 0 || threshold as usize > members.len() {
+        RegistryError::InvalidThreshold(threshold).panic();
+    }
+}
@@ -0,0 +1,194 @@
+//! Errors of the Registry. A panic message reads `Registry: <message> (E<code>)`, codes are
+//! stable while messages may be reworded.
+use std::fmt;
+
+use near_sdk::{env, AccountId};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RegistryError {
+    // State and upgrades.
+    AlreadyInitialized,
+    NotInitialized,
+    UnsupportedStateVersion(u32),
+    InvalidStateVersion,
+    PrivateMethod(&'static str),
+    InvalidCodeHash,
+    UpgradeNotApproved,
+
+    // Access control.
+    NotGovernance,
+    NotCouncilMember,
+    GovernedByCouncil,
+    NotGuardian,
+
+    // Validators.
+    ValidatorAlreadyExists,
+    ValidatorNotFound,
+    InvalidPoolAccountId,
+    InvalidCommission(u32),
+    WeightsSumMismatch(u64),
+    DuplicateValidator(AccountId),
+    NoActiveWeights,
+    AllocationOverflow,
+
+    // Council.
+    CouncilNotEnabled,
+    InvalidThreshold(u32),
+    InvalidMemberAccountId,
+    DuplicateMember(AccountId),
+    InvalidProposalPeriod,
+    ProposalNotFound(u64),
+    ProposalExpired(u64),
+    AlreadyConfirmed(u64),
+
+    // Timelock.
+    InvalidTimelockDelay,
+    InvalidGuardianAccountId,
+    QueuedChangeNotFound(u64),
+    TimelockNotReady(u64, u64),
+    TimelockDelayTooLong(u64),
+    EffectiveEpochOverflow,
+}
+
+impl RegistryError {
+    pub fn code(&self) -> u16 {
+        match self {
+            RegistryError::AlreadyInitialized => 100,
+            RegistryError::NotInitialized => 101,
+            RegistryError::UnsupportedStateVersion(_) => 102,
+            RegistryError::InvalidStateVersion => 103,
+            RegistryError::PrivateMethod(_) => 104,
+            RegistryError::InvalidCodeHash => 105,
+            RegistryError::UpgradeNotApproved => 106,
+
+            RegistryError::NotGovernance => 200,
+            RegistryError::NotCouncilMember => 201,
+            RegistryError::GovernedByCouncil => 202,
+            RegistryError::NotGuardian => 203,
+
+            RegistryError::ValidatorAlreadyExists => 300,
+            RegistryError::ValidatorNotFound => 301,
+            RegistryError::InvalidPoolAccountId => 302,
+            RegistryError::InvalidCommission(_) => 303,
+            RegistryError::WeightsSumMismatch(_) => 304,
+            RegistryError::DuplicateValidator(_) => 305,
+            RegistryError::NoActiveWeights => 306,
+            RegistryError::AllocationOverflow => 307,
+
+            RegistryError::CouncilNotEnabled => 400,
+            RegistryError::InvalidThreshold(_) => 401,
+            RegistryError::InvalidMemberAccountId => 402,
+            RegistryError::DuplicateMember(_) => 403,
+            RegistryError::InvalidProposalPeriod => 404,
+            RegistryError::ProposalNotFound(_) => 405,
+            RegistryError::ProposalExpired(_) => 406,
+            RegistryError::AlreadyConfirmed(_) => 407,
+
+            RegistryError::InvalidTimelockDelay => 500,
+            RegistryError::InvalidGuardianAccountId => 501,
+            RegistryError::QueuedChangeNotFound(_) => 502,
+            RegistryError::TimelockNotReady(_, _) => 503,
+            RegistryError::TimelockDelayTooLong(_) => 504,
+            RegistryError::EffectiveEpochOverflow => 505,
+        }
+    }
+
+    /// Extracts the code from a panic message of the contract.
+    pub fn parse_code(message: &str) -> Option<u16> {
+        let start = message.rfind("(E")? + 2;
+        let end = start + message[start..].find(')')?;
+        message[start..end].parse().ok()
+    }
+
+    pub(crate) fn panic(&self) -> ! {
+        env::panic(self.to_string().as_bytes())
+    }
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Registry: ")?;
+        match self {
+            RegistryError::AlreadyInitialized => write!(f, "Already initialized"),
+            RegistryError::NotInitialized => write!(f, "Registry should be initialized before usage"),
+            RegistryError::UnsupportedStateVersion(version) => {
+                write!(f, "Unsupported state version {}", version)
+            }
+            RegistryError::InvalidStateVersion => write!(f, "Cannot deserialize the state version"),
+            RegistryError::PrivateMethod(method) => write!(f, "Method {} is private", method),
+            RegistryError::InvalidCodeHash => write!(f, "The code hash should be 32 bytes"),
+            RegistryError::UpgradeNotApproved => {
+                write!(f, "The code is not approved, approve its hash with upgrade first")
+            }
+
+            RegistryError::NotGovernance => write!(f, "Caller is not Governance"),
+            RegistryError::NotCouncilMember => write!(f, "Caller is not a council member"),
+            RegistryError::GovernedByCouncil => {
+                write!(f, "The Registry is governed by the council, submit a proposal")
+            }
+            RegistryError::NotGuardian => write!(f, "Caller is not the guardian"),
+
+            RegistryError::ValidatorAlreadyExists => write!(f, "Already exist Validator"),
+            RegistryError::ValidatorNotFound => write!(f, "Non-exist Validator"),
+            RegistryError::InvalidPoolAccountId => write!(f, "Invalid staking pool account id"),
+            RegistryError::InvalidCommission(commission) => {
+                write!(f, "Commission {} is more than 10000 basis points", commission)
+            }
+            RegistryError::WeightsSumMismatch(total) => write!(
+                f,
+                "Weights of active validators sum to {} instead of 10000 basis points",
+                total
+            ),
+            RegistryError::DuplicateValidator(validator) => {
+                write!(f, "The validator {} is listed more than once", validator)
+            }
+            RegistryError::NoActiveWeights => write!(f, "No active validator has a weight"),
+            RegistryError::AllocationOverflow => {
+                write!(f, "The stakes and the amount to allocate overflow u128")
+            }
+
+            RegistryError::CouncilNotEnabled => write!(f, "The council is not enabled"),
+            RegistryError::InvalidThreshold(threshold) => write!(
+                f,
+                "Threshold {} is not between one and the number of members",
+                threshold
+            ),
+            RegistryError::InvalidMemberAccountId => write!(f, "Invalid council member account id"),
+            RegistryError::DuplicateMember(member) => {
+                write!(f, "The member {} is listed more than once", member)
+            }
+            RegistryError::InvalidProposalPeriod => {
+                write!(f, "The proposal period should be at least one epoch")
+            }
+            RegistryError::ProposalNotFound(proposal_id) => {
+                write!(f, "Proposal {} doesn't exist", proposal_id)
+            }
+            RegistryError::ProposalExpired(proposal_id) => {
+                write!(f, "Proposal {} has expired", proposal_id)
+            }
+            RegistryError::AlreadyConfirmed(proposal_id) => {
+                write!(f, "Proposal {} is already confirmed by the caller", proposal_id)
+            }
+
+            RegistryError::InvalidTimelockDelay => {
+                write!(f, "The timelock delay should be at least one epoch")
+            }
+            RegistryError::InvalidGuardianAccountId => write!(f, "Invalid guardian account id"),
+            RegistryError::QueuedChangeNotFound(queued_id) => {
+                write!(f, "Queued change {} doesn't exist", queued_id)
+            }
+            RegistryError::TimelockNotReady(queued_id, effective_epoch) => write!(
+                f,
+                "Queued change {} takes effect at epoch {}",
+                queued_id, effective_epoch
+            ),
+            RegistryError::TimelockDelayTooLong(max) => {
+                write!(f, "The timelock delay should be at most {} epochs", max)
+            }
+            RegistryError::EffectiveEpochOverflow => {
+                write!(f, "The effective epoch of the change overflows")
+            }
+        }?;
+        write!(f, " (E{})", self.code())
+    }
+}
@@ -4,18 +4,60 @@ use near_sdk::{
     env, near_bindgen, AccountId
 };
 
+mod action;
+mod allocation;
+mod council;
+mod errors;
+mod timelock;
+mod upgrade;
+pub mod util;
+mod validator;
+mod weights;
+
+pub use action::RegistryAction;
+pub use council::{Council, Proposal};
+pub use errors::RegistryError;
+pub use timelock::{QueuedChange, Timelock, DEFAULT_TIMELOCK_EPOCHS, MAX_TIMELOCK_EPOCHS};
+pub use upgrade::{RegistryV1, VersionedRegistry, STATE_VERSION};
+pub use validator::{ValidatorInfo, ValidatorStatus, BASIS_POINTS};
+
+/// Prefixes of all persistent collections. A prefix starts with the variant index, so key
+/// spaces of different collections never overlap. New variants must be appended at the end.
+#[derive(BorshSerialize)]
+pub(crate) enum StorageKey {
+    Validators,
+    Proposals,
+    QueuedChanges,
+}
+
+impl StorageKey {
+    pub(crate) fn into_prefix(self) -> Vec<u8> {
+        self.try_to_vec().unwrap()
+    }
+}
+
 // @TODO: Dynamic Ratio
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Registry {
     pub governance: AccountId,
-    pub validator_info: UnorderedMap<AccountId, u32>,
+    pub validator_info: UnorderedMap<AccountId, ValidatorInfo>,
     pub validator_count: u32,
+    /// Once set, changes go through council proposals instead of Governance.
+    pub council: Option<Council>,
+    pub proposals: UnorderedMap<u64, Proposal>,
+    pub next_proposal_id: u64,
+    /// Changes are queued and take effect after a delay, see `DEFAULT_TIMELOCK_EPOCHS`.
+    pub timelock: Option<Timelock>,
+    pub queued_changes: UnorderedMap<u64, QueuedChange>,
+    pub next_queued_id: u64,
+    /// sha256 of the code `deploy_upgrade` can deploy.
+    pub approved_code_hash: Option<Vec<u8>>,
 }
 
 impl Default for Registry {
     fn default() -> Self {
-        panic!("Registry should be initialized before usage")
+        RegistryError::NotInitialized.panic()
     }
 }
 
@@ -23,60 +65,118 @@ impl Default for Registry {
 impl Registry {
     #[init]
     pub fn new (governance: AccountId) -> Self {
-        assert!(!env::state_exists(), "Registry: Already initialized");
+        if env::state_exists() {
+            RegistryError::AlreadyInitialized.panic();
+        }
+        upgrade::write_state_version();
         Self {
-            governance,
-            validator_info: UnorderedMap::new(b"a".to_vec()),
-            validator_count: 0
+            governance: governance.clone(),
+            validator_info: UnorderedMap::new(StorageKey::Validators.into_prefix()),
+            validator_count: 0,
+            council: None,
+            proposals: UnorderedMap::new(StorageKey::Proposals.into_prefix()),
+            next_proposal_id: 0,
+            timelock: Some(Timelock::with_default_delay(governance)),
+            queued_changes: UnorderedMap::new(StorageKey::QueuedChanges.into_prefix()),
+            next_queued_id: 0,
+            approved_code_hash: None,
         }
     }
 
-    pub fn add_validator(&mut self, validator: AccountId, ratio: u32) {
-        let caller = env::predecessor_account_id();
-        if caller != self.governance {
-            env::panic(b"Registry: Caller is not Governance");
-        }
-        if self.validator_info.insert(&validator, &ratio).is_none() {
-            self.validator_count += 1;
-        } else {
-            env::panic(b"Registry: Already exist Validator");
-        }
+    /// Registers `validator` with `info`. `added_at` is set to the current epoch height.
+    pub fn add_validator(&mut self, validator: AccountId, info: ValidatorInfo) {
+        self.assert_governance();
+        self.dispatch_action(RegistryAction::AddValidator { validator, info });
     }
 
+    /// Removes `validator`. The weight of an active validator is split over the other active
+    /// validators in proportion to their weights.
     pub fn del_validator(&mut self, validator: AccountId) {
-        let caller = env::predecessor_account_id();
-        if caller != self.governance {
-            env::panic(b"Registry: Caller is not Governance");
+        self.assert_governance();
+        self.dispatch_action(RegistryAction::DelValidator { validator });
+    }
+
+    /// Replaces the record of `validator` with `info`, keeping the epoch it was added at.
+    /// A validator that stops being active hands its weight over as in `del_validator`.
+    pub fn update_validator(&mut self, validator: AccountId, info: ValidatorInfo) {
+        self.assert_governance();
+        self.dispatch_action(RegistryAction::UpdateValidator { validator, info });
+    }
+
+    pub fn get_validators(&self) -> Vec<(AccountId, ValidatorInfo)> {
+        self.validator_info.to_vec()
+    }
+
+    pub fn get_validator(&self, validator: AccountId) -> Option<ValidatorInfo> {
+        self.validator_info.get(&validator)
+    }
+
+    pub fn get_validator_ratio(&self, validator: AccountId) -> Option<u32> {
+        self.validator_info.get(&validator).map(|info| info.ratio)
+    }
+}
+
+impl Registry {
+    /// Panics unless the caller is Governance and no council governs the Registry.
+    pub(crate) fn assert_governance(&self) {
+        if env::predecessor_account_id() != self.governance {
+            RegistryError::NotGovernance.panic();
         }
-        if self.validator_info.remove(&validator).is_some() {
-            self.validator_count -= 1;
-        } else {
-            env::panic(b"Registry: Non-exist Validator");
+        if self.council.is_some() {
+            RegistryError::GovernedByCouncil.panic();
         }
     }
 
-    pub fn update_validator(&mut self, validator: AccountId, ratio: u32) {
-        let caller = env::predecessor_account_id();
-        if caller != self.governance {
-            env::panic(b"Registry: Caller is not Governance");
-        }
-        if self.validator_info.insert(&validator, &ratio).is_none() {
-            env::panic(b"Registry: Non-exist Validator");
+    pub(crate) fn internal_add_validator(&mut self, validator: AccountId, info: ValidatorInfo) {
+        info.assert_valid();
+        if self.validator_info.get(&validator).is_some() {
+            RegistryError::ValidatorAlreadyExists.panic();
         }
+        let info = ValidatorInfo {
+            added_at: env::epoch_height(),
+            ..info
+        };
+        self.validator_info.insert(&validator, &info);
+        self.validator_count += 1;
+        self.assert_weights();
     }
 
-    pub fn get_validators(&self) -> Vec<(AccountId, u32)> {
-        self.validator_info.to_vec()
+    pub(crate) fn internal_del_validator(&mut self, validator: AccountId) {
+        let info = self
+            .validator_info
+            .remove(&validator)
+            .unwrap_or_else(|| RegistryError::ValidatorNotFound.panic());
+        self.validator_count -= 1;
+        if info.is_active() {
+            self.redistribute_weight(info.ratio);
+        }
+        self.assert_weights();
     }
 
-    pub fn get_validator_ratio(&self, validator: AccountId) -> Option<u32> {
-        self.validator_info.get(&validator)
+    pub(crate) fn internal_update_validator(&mut self, validator: AccountId, info: ValidatorInfo) {
+        info.assert_valid();
+        let current = self
+            .validator_info
+            .get(&validator)
+            .unwrap_or_else(|| RegistryError::ValidatorNotFound.panic());
+        let deactivated = current.is_active() && !info.is_active();
+        let info = ValidatorInfo {
+            ratio: if deactivated { 0 } else { info.ratio },
+            added_at: current.added_at,
+            ..info
+        };
+        self.validator_info.insert(&validator, &info);
+        if deactivated {
+            self.redistribute_weight(current.ratio);
+        }
+        self.assert_weights();
     }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg(test)]
 mod tests {
+    use near_sdk::json_types::Base64VecU8;
     use near_sdk::MockedBlockchain;
     use near_sdk::{testing_env, VMContext};
 
@@ -96,15 +196,76 @@ mod tests {
         "alice.near".to_string()
     }
 
-    // fn bob() -> AccountId {
-    //     "bob.near".to_string()
-    // }
+    fn bob() -> AccountId {
+        "bob.near".to_string()
+    }
 
-    // fn carol() -> AccountId {
-    //     "carol.near".to_string()
-    // }
+    fn carol() -> AccountId {
+        "carol.near".to_string()
+    }
     // end of Validator List
 
+    fn dave() -> AccountId {
+        "dave.near".to_string()
+    }
+
+    fn erin() -> AccountId {
+        "erin.near".to_string()
+    }
+
+    fn frank() -> AccountId {
+        "frank.near".to_string()
+    }
+
+    fn validator_info(ratio: u32) -> ValidatorInfo {
+        ValidatorInfo {
+            pool_account_id: "alice.pool.near".to_string(),
+            ratio,
+            status: ValidatorStatus::Active,
+            commission: 500,
+            display_name: "Alice".to_string(),
+            added_at: 0,
+        }
+    }
+
+    /// Registry without the default timelock, so changes take effect right away.
+    fn registry() -> Registry {
+        let mut contract = Registry::new(governance());
+        contract.timelock = None;
+        contract
+    }
+
+    /// Registry governed by a 2-of-3 council of dave, erin and frank.
+    fn council_registry(context: &mut VMContext) -> Registry {
+        context.predecessor_account_id = deployer();
+        testing_env!(context.clone());
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.enable_council(vec![dave(), erin(), frank()], 2, 2);
+        contract
+    }
+
+    fn guardian() -> AccountId {
+        "guardian.near".to_string()
+    }
+
+    /// Registry whose changes are delayed by two epochs.
+    fn timelocked_registry(context: &mut VMContext) -> Registry {
+        context.predecessor_account_id = deployer();
+        testing_env!(context.clone());
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.set_timelock(2, guardian());
+        contract
+    }
+
+    fn set_caller(context: &mut VMContext, account_id: AccountId) {
+        context.predecessor_account_id = account_id;
+        testing_env!(context.clone());
+    }
+
     fn get_context(predecessor_account_id: AccountId) -> VMContext {
         VMContext {
             current_account_id: governance(),
@@ -130,21 +291,41 @@ mod tests {
     fn test_initialize_new_registry() {
         let context = get_context(deployer());
         testing_env!(context);
-        let contract = Registry::new(governance());
+        let contract = registry();
         assert_eq!(contract.validator_count, 0);
     }
 
+    #[test]
+    fn test_new_registry_queues_changes_by_default() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+        let mut contract = Registry::new(governance());
+        let timelock = Timelock {
+            delay: DEFAULT_TIMELOCK_EPOCHS,
+            guardian: governance(),
+        };
+        assert_eq!(contract.get_timelock(), Some(timelock));
+
+        // Replacing the default timelock waits for the default delay as well.
+        set_caller(&mut context, governance());
+        contract.set_timelock(5, guardian());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+        assert_eq!(contract.get_validator(alice()), None);
+        assert_eq!(contract.get_queued_changes(0, 10).len(), 2);
+        assert_eq!(contract.get_timelock().unwrap().delay, DEFAULT_TIMELOCK_EPOCHS);
+    }
+
     #[test]
     fn test_add_validator_with_get_ratio() {
-        let _ratio: u32 = 10;
+        let _ratio: u32 = BASIS_POINTS;
 
         let mut context = get_context(deployer());
         testing_env!(context.clone());
 
-        let mut contract = Registry::new(governance());
+        let mut contract = registry();
         context.predecessor_account_id = governance();
         testing_env!(context.clone());
-        contract.add_validator(alice(), _ratio);
+        contract.add_validator(alice(), validator_info(_ratio));
 
         assert_eq!(contract.validator_count, 1);
         assert_eq!(contract.get_validator_ratio(alice()), Some(_ratio));
@@ -158,37 +339,37 @@ mod tests {
         let context = get_context(deployer());
         testing_env!(context);
 
-        let mut contract = Registry::new(governance());
-        contract.add_validator(alice(), _ratio);
+        let mut contract = registry();
+        contract.add_validator(alice(), validator_info(_ratio));
     }
 
     #[test]
     fn test_add_validator_with_get_all_validators() {
-        let _ratio: u32 = 10;
+        let _ratio: u32 = BASIS_POINTS;
 
         let mut context = get_context(deployer());
         testing_env!(context.clone());
 
-        let mut contract = Registry::new(governance());
+        let mut contract = registry();
         context.predecessor_account_id = governance();
         testing_env!(context.clone());
-        contract.add_validator(alice(), _ratio);
+        contract.add_validator(alice(), validator_info(_ratio));
         assert_eq!(contract.validator_count, 1);
-        assert_eq!(contract.get_validators(), vec![(alice(), _ratio)]);
+        assert_eq!(contract.get_validators(), vec![(alice(), validator_info(_ratio))]);
     }
 
     #[test]
     fn test_del_validator() {
-        let _ratio: u32 = 10;
+        let _ratio: u32 = BASIS_POINTS;
 
         let mut context = get_context(deployer());
         testing_env!(context.clone());
 
-        let mut contract = Registry::new(governance());
+        let mut contract = registry();
         context.predecessor_account_id = governance();
         testing_env!(context.clone());
 
-        contract.add_validator(alice(), _ratio);
+        contract.add_validator(alice(), validator_info(_ratio));
         contract.del_validator(alice());
         assert_eq!(contract.validator_count, 0);
         assert_eq!(contract.get_validators(), vec![]);
@@ -200,7 +381,7 @@ mod tests {
         let context = get_context(deployer());
         testing_env!(context);
 
-        let mut contract = Registry::new(governance());
+        let mut contract = registry();
         contract.del_validator(alice());
     }
 
@@ -210,7 +391,7 @@ mod tests {
         let mut context = get_context(deployer());
         testing_env!(context.clone());
 
-        let mut contract = Registry::new(governance());
+        let mut contract = registry();
         context.predecessor_account_id = governance();
         testing_env!(context.clone());
         contract.del_validator(alice());
@@ -218,18 +399,18 @@ mod tests {
 
     #[test]
     fn test_update_validator() {
-        let _ratio = 10u32;
+        let _ratio = 0u32;
         let mut context = get_context(deployer());
         testing_env!(context.clone());
 
-        let mut contract = Registry::new(governance());
+        let mut contract = registry();
         context.predecessor_account_id = governance();
         testing_env!(context.clone());
 
-        contract.add_validator(alice(), _ratio);
-        contract.update_validator(alice(), _ratio + 11u32);
+        contract.add_validator(alice(), validator_info(_ratio));
+        contract.update_validator(alice(), validator_info(BASIS_POINTS));
 
-        assert_eq!(contract.get_validator_ratio(alice()), Some(21u32));
+        assert_eq!(contract.get_validator_ratio(alice()), Some(BASIS_POINTS));
     }
 
     #[test]
@@ -239,10 +420,10 @@ mod tests {
         let context = get_context(deployer());
         testing_env!(context.clone());
 
-        let mut contract = Registry::new(governance());
+        let mut contract = registry();
         testing_env!(context.clone());
 
-        contract.update_validator(alice(), _ratio + 11u32);
+        contract.update_validator(alice(), validator_info(_ratio + 11u32));
     }
 
     #[test]
@@ -252,11 +433,11 @@ mod tests {
         let mut context = get_context(deployer());
         testing_env!(context.clone());
 
-        let mut contract = Registry::new(governance());
+        let mut contract = registry();
         context.predecessor_account_id = governance();
         testing_env!(context.clone());
 
-        contract.update_validator(alice(), _ratio + 11u32);
+        contract.update_validator(alice(), validator_info(_ratio + 11u32));
 
         // assert_eq!(contract.get_validator_ratio(alice()), Some(21u32));
     }
@@ -266,10 +447,741 @@ mod tests {
         let context = get_context(deployer());
         testing_env!(context.clone());
 
-        let contract = Registry::new(governance());
+        let contract = registry();
         assert_eq!(contract.get_validator_ratio(alice()), None);
     }
 
+    #[test]
+    fn test_error_codes() {
+        let error = RegistryError::NotGovernance;
+        assert_eq!(error.code(), 200);
+        assert_eq!(error.to_string(), "Registry: Caller is not Governance (E200)");
+        assert_eq!(RegistryError::parse_code(&error.to_string()), Some(200));
+    }
+
+    #[test]
+    fn test_get_validator() {
+        let mut context = get_context(deployer());
+        context.epoch_height = 7;
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        // added_at is set by the Registry.
+        contract.add_validator(alice(), ValidatorInfo { added_at: 3, ..validator_info(10_000) });
+
+        let expected = ValidatorInfo { added_at: 7, ..validator_info(10_000) };
+        assert_eq!(contract.get_validator(alice()), Some(expected.clone()));
+        assert_eq!(contract.get_validator(bob()), None);
+        assert_eq!(
+            near_sdk::serde_json::to_string(&expected).unwrap(),
+            "{\"pool_account_id\":\"alice.pool.near\",\"ratio\":10000,\"status\":\"active\",\
+             \"commission\":500,\"display_name\":\"Alice\",\"added_at\":7}"
+        );
+    }
+
+    #[test]
+    fn test_update_validator_keeps_added_at() {
+        let mut context = get_context(deployer());
+        context.epoch_height = 7;
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+
+        context.epoch_height = 9;
+        testing_env!(context.clone());
+        let info = ValidatorInfo {
+            status: ValidatorStatus::Draining,
+            commission: 1_000,
+            ..validator_info(BASIS_POINTS)
+        };
+        contract.update_validator(alice(), info.clone());
+
+        assert_eq!(contract.get_validator(alice()), Some(ValidatorInfo { added_at: 7, ..info }));
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: Commission 10001 is more than 10000 basis points")]
+    fn test_add_validator_with_invalid_commission() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), ValidatorInfo { commission: 10_001, ..validator_info(10) });
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: Invalid staking pool account id")]
+    fn test_update_validator_with_invalid_pool_account_id() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+        let info = ValidatorInfo {
+            pool_account_id: "Invalid Pool".to_string(),
+            ..validator_info(BASIS_POINTS)
+        };
+        contract.update_validator(alice(), info);
+    }
+
+    #[test]
+    fn test_set_weights() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+        contract.add_validator(bob(), validator_info(0));
+        // Weights of inactive validators are not counted.
+        let paused = ValidatorInfo {
+            status: ValidatorStatus::Paused,
+            ..validator_info(500)
+        };
+        contract.add_validator(carol(), paused);
+
+        contract.set_weights(vec![(alice(), 4_000), (bob(), 6_000)]);
+        assert_eq!(contract.get_validator_ratio(alice()), Some(4_000));
+        assert_eq!(contract.get_normalized_weights(), vec![(alice(), 4_000), (bob(), 6_000)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "sum to 9000 instead of 10000 basis points")]
+    fn test_set_weights_with_wrong_sum() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+        contract.add_validator(bob(), validator_info(0));
+
+        contract.set_weights(vec![(alice(), 4_000), (bob(), 5_000)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: The validator alice.near is listed more than once")]
+    fn test_set_weights_with_duplicate_validator() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+
+        contract.set_weights(vec![(alice(), 4_000), (alice(), 6_000)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: Caller is not Governance")]
+    fn test_set_weights_with_non_governance_call() {
+        let context = get_context(deployer());
+        testing_env!(context);
+
+        let mut contract = registry();
+        contract.set_weights(vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "sum to 10001 instead of 10000 basis points")]
+    fn test_add_validator_breaking_weights() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+        contract.add_validator(bob(), validator_info(1));
+    }
+
+    #[test]
+    fn test_del_weighted_validator() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+        contract.add_validator(bob(), validator_info(0));
+        contract.add_validator(carol(), validator_info(0));
+        contract.set_weights(vec![(alice(), 5_000), (bob(), 2_000), (carol(), 3_000)]);
+
+        contract.del_validator(alice());
+        assert_eq!(contract.get_normalized_weights(), vec![(carol(), 6_000), (bob(), 4_000)]);
+        contract.del_validator(carol());
+        assert_eq!(contract.get_normalized_weights(), vec![(bob(), BASIS_POINTS)]);
+    }
+
+    #[test]
+    fn test_pause_weighted_validator() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+        contract.add_validator(bob(), validator_info(0));
+        contract.add_validator(carol(), validator_info(0));
+        contract.set_weights(vec![(alice(), 3_333), (bob(), 3_333), (carol(), 3_334)]);
+
+        let paused = ValidatorInfo {
+            status: ValidatorStatus::Paused,
+            ..validator_info(3_333)
+        };
+        contract.update_validator(alice(), paused);
+        assert_eq!(contract.get_validator_ratio(alice()), Some(0));
+        assert_eq!(contract.get_normalized_weights(), vec![(bob(), 4_999), (carol(), 5_001)]);
+    }
+
+    #[test]
+    fn test_split_with_largest_remainder() {
+        assert_eq!(weights::split(10, &[1, 1, 1]), vec![4, 3, 3]);
+        assert_eq!(weights::split(10, &[1, 2, 2]), vec![2, 4, 4]);
+        assert_eq!(weights::split(11, &[1, 2, 2]), vec![2, 5, 4]);
+        assert_eq!(weights::split(10, &[0, 0]), vec![0, 0]);
+        let total = 10u128.pow(33) + 1;
+        let parts = weights::split(total, &[u128::from(u32::MAX), 3, 7]);
+        assert_eq!(parts.iter().sum::<u128>(), total);
+    }
+
+    #[test]
+    fn test_compute_allocation() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+        contract.add_validator(bob(), validator_info(0));
+        let paused = ValidatorInfo {
+            status: ValidatorStatus::Paused,
+            ..validator_info(BASIS_POINTS)
+        };
+        contract.add_validator(carol(), paused);
+        contract.set_weights(vec![(alice(), 3_333), (bob(), 6_667)]);
+
+        // The yocto left by rounding goes to the largest remainder.
+        assert_eq!(
+            contract.compute_allocation(10.into(), None),
+            vec![(alice(), 3.into()), (bob(), 7.into())]
+        );
+        assert_eq!(
+            contract.compute_allocation(0.into(), None),
+            vec![(alice(), 0.into()), (bob(), 0.into())]
+        );
+    }
+
+    #[test]
+    fn test_compute_allocation_with_current_stake() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+        contract.add_validator(bob(), validator_info(0));
+        contract.set_weights(vec![(alice(), 5_000), (bob(), 5_000)]);
+        // Stake of an unknown validator is ignored.
+        let stake = vec![(alice(), 100.into()), (bob(), 0.into()), (carol(), 1_000.into())];
+
+        // bob is under-allocated and filled first.
+        assert_eq!(
+            contract.compute_allocation(50.into(), Some(stake.clone())),
+            vec![(alice(), 0.into()), (bob(), 50.into())]
+        );
+        assert_eq!(
+            contract.compute_allocation(300.into(), Some(stake.clone())),
+            vec![(alice(), 100.into()), (bob(), 200.into())]
+        );
+        // Targets are rounded down, what is left after the deficits is split by the weights.
+        let stake = vec![(alice(), 1.into())];
+        assert_eq!(
+            contract.compute_allocation(2.into(), Some(stake)),
+            vec![(alice(), 1.into()), (bob(), 1.into())]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: No active validator has a weight")]
+    fn test_compute_allocation_without_weights() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(0));
+
+        contract.compute_allocation(10.into(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: The stakes and the amount to allocate overflow u128")]
+    fn test_compute_allocation_overflow() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let mut contract = registry();
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+        let stake = vec![(alice(), u128::MAX.into())];
+
+        contract.compute_allocation(1.into(), Some(stake));
+    }
+
+    #[test]
+    fn test_council_executes_on_threshold() {
+        let mut context = get_context(deployer());
+        let mut contract = council_registry(&mut context);
+        let council = Council {
+            members: vec![dave(), erin(), frank()],
+            threshold: 2,
+            proposal_period: 2,
+        };
+        assert_eq!(contract.get_council(), Some(council));
+
+        set_caller(&mut context, dave());
+        let action = RegistryAction::AddValidator {
+            validator: alice(),
+            info: validator_info(BASIS_POINTS),
+        };
+        let proposal_id = contract.propose(action.clone());
+        assert_eq!(contract.get_validator(alice()), None);
+        let proposal = Proposal {
+            proposer: dave(),
+            action,
+            confirmations: vec![dave()],
+            expires_at: 2,
+        };
+        assert_eq!(contract.get_proposals(0, 10), vec![(proposal_id, proposal)]);
+
+        set_caller(&mut context, erin());
+        assert!(contract.confirm(proposal_id));
+        assert_eq!(contract.get_validator_ratio(alice()), Some(BASIS_POINTS));
+        assert_eq!(contract.get_proposal(proposal_id), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: The Registry is governed by the council")]
+    fn test_governance_call_in_council_mode() {
+        let mut context = get_context(deployer());
+        let mut contract = council_registry(&mut context);
+
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: Caller is not a council member")]
+    fn test_propose_with_non_member() {
+        let mut context = get_context(deployer());
+        let mut contract = council_registry(&mut context);
+
+        set_caller(&mut context, alice());
+        contract.propose(RegistryAction::DelValidator { validator: alice() });
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: Proposal 0 is already confirmed by the caller")]
+    fn test_confirm_twice() {
+        let mut context = get_context(deployer());
+        let mut contract = council_registry(&mut context);
+
+        set_caller(&mut context, dave());
+        let proposal_id = contract.propose(RegistryAction::DelValidator { validator: alice() });
+        contract.confirm(proposal_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: Proposal 0 has expired")]
+    fn test_confirm_expired_proposal() {
+        let mut context = get_context(deployer());
+        let mut contract = council_registry(&mut context);
+
+        set_caller(&mut context, dave());
+        let proposal_id = contract.propose(RegistryAction::DelValidator { validator: alice() });
+        context.epoch_height = 2;
+        set_caller(&mut context, erin());
+        contract.confirm(proposal_id);
+    }
+
+    #[test]
+    fn test_remove_expired_proposals() {
+        let mut context = get_context(deployer());
+        let mut contract = council_registry(&mut context);
+
+        set_caller(&mut context, dave());
+        let expired = contract.propose(RegistryAction::DelValidator { validator: alice() });
+        context.epoch_height = 1;
+        testing_env!(context.clone());
+        let open = contract.propose(RegistryAction::DelValidator { validator: bob() });
+
+        context.epoch_height = 2;
+        set_caller(&mut context, alice());
+        assert_eq!(contract.remove_expired_proposals(vec![expired, open, 7]), 1);
+        assert_eq!(contract.get_proposals(0, 10).len(), 1);
+        assert!(contract.get_proposal(open).is_some());
+    }
+
+    #[test]
+    fn test_get_proposals_with_pagination() {
+        let mut context = get_context(deployer());
+        let mut contract = council_registry(&mut context);
+
+        set_caller(&mut context, dave());
+        for validator in vec![alice(), bob(), carol()] {
+            contract.propose(RegistryAction::DelValidator { validator });
+        }
+        let ids: Vec<u64> = contract.get_proposals(1, 5).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![1, 2]);
+        assert_eq!(contract.get_proposals(3, 5), vec![]);
+    }
+
+    #[test]
+    fn test_council_change_members() {
+        let mut context = get_context(deployer());
+        let mut contract = council_registry(&mut context);
+
+        set_caller(&mut context, frank());
+        let add_alice = contract.propose(RegistryAction::AddValidator {
+            validator: alice(),
+            info: validator_info(BASIS_POINTS),
+        });
+        set_caller(&mut context, dave());
+        let remove_frank =
+            contract.propose(RegistryAction::ChangeMembers { members: vec![dave(), erin()] });
+        set_caller(&mut context, erin());
+        assert!(contract.confirm(remove_frank));
+        assert_eq!(contract.get_council().unwrap().members, vec![dave(), erin()]);
+
+        // The confirmation of frank no longer counts.
+        set_caller(&mut context, dave());
+        assert!(!contract.confirm(add_alice));
+        set_caller(&mut context, erin());
+        assert!(contract.confirm(add_alice));
+        assert!(contract.get_validator(alice()).is_some());
+
+        // A threshold of one executes on proposal.
+        set_caller(&mut context, dave());
+        let change_threshold = contract.propose(RegistryAction::ChangeThreshold { threshold: 1 });
+        set_caller(&mut context, erin());
+        contract.confirm(change_threshold);
+        contract.propose(RegistryAction::DelValidator { validator: alice() });
+        assert_eq!(contract.get_validator(alice()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: Threshold 4 is not between one and the number of members")]
+    fn test_council_change_threshold_above_members() {
+        let mut context = get_context(deployer());
+        let mut contract = council_registry(&mut context);
+
+        set_caller(&mut context, dave());
+        let proposal_id = contract.propose(RegistryAction::ChangeThreshold { threshold: 4 });
+        set_caller(&mut context, erin());
+        contract.confirm(proposal_id);
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: The member dave.near is listed more than once")]
+    fn test_enable_council_with_duplicate_member() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+        let mut contract = registry();
+        set_caller(&mut context, governance());
+
+        contract.enable_council(vec![dave(), erin(), dave()], 2, 2);
+    }
+
+    #[test]
+    fn test_timelock_queues_changes() {
+        let mut context = get_context(deployer());
+        let mut contract = timelocked_registry(&mut context);
+        let timelock = Timelock {
+            delay: 2,
+            guardian: guardian(),
+        };
+        assert_eq!(contract.get_timelock(), Some(timelock));
+
+        context.epoch_height = 1;
+        testing_env!(context.clone());
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+        assert_eq!(contract.get_validator(alice()), None);
+        let change = QueuedChange {
+            action: RegistryAction::AddValidator {
+                validator: alice(),
+                info: validator_info(BASIS_POINTS),
+            },
+            queued_at: 1,
+            effective_epoch: 3,
+        };
+        assert_eq!(contract.get_queued_changes(0, 10), vec![(0, change)]);
+
+        // Anyone can execute once the delay has passed.
+        context.epoch_height = 3;
+        set_caller(&mut context, bob());
+        contract.execute_queued(0);
+        assert_eq!(contract.get_validator(alice()).unwrap().added_at, 3);
+        assert_eq!(contract.get_queued_changes(0, 10), vec![]);
+    }
+
+    #[test]
+    fn test_timelock_queues_upgrades() {
+        let mut context = get_context(deployer());
+        let mut contract = timelocked_registry(&mut context);
+
+        let code = vec![1u8; 8];
+        contract.upgrade(Base64VecU8(env::sha256(&code)));
+        contract.upgrade(Base64VecU8(env::sha256(&[2u8; 8])));
+        assert_eq!(contract.get_approved_code_hash(), None);
+        let ids: Vec<u64> = contract
+            .get_queued_changes(1, 5)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec![1]);
+
+        // The guardian cancels the second upgrade, the first one is deployed.
+        set_caller(&mut context, guardian());
+        contract.cancel_queued(1);
+        context.epoch_height = 2;
+        set_caller(&mut context, bob());
+        contract.execute_queued(0);
+        assert_eq!(contract.get_approved_code_hash(), Some(Base64VecU8(env::sha256(&code))));
+        contract.deploy_upgrade(Base64VecU8(code));
+        assert_eq!(contract.get_queued_changes(0, 5), vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: Queued change 0 takes effect at epoch 2")]
+    fn test_execute_queued_before_effective_epoch() {
+        let mut context = get_context(deployer());
+        let mut contract = timelocked_registry(&mut context);
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+
+        context.epoch_height = 1;
+        testing_env!(context.clone());
+        contract.execute_queued(0);
+    }
+
+    #[test]
+    fn test_cancel_queued() {
+        let mut context = get_context(deployer());
+        let mut contract = timelocked_registry(&mut context);
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+        contract.set_timelock(1, governance());
+
+        set_caller(&mut context, guardian());
+        contract.cancel_queued(1);
+        assert!(contract.get_queued_change(0).is_some());
+        assert_eq!(contract.get_queued_change(1), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: Caller is not the guardian")]
+    fn test_cancel_queued_with_non_guardian_call() {
+        let mut context = get_context(deployer());
+        let mut contract = timelocked_registry(&mut context);
+        contract.add_validator(alice(), validator_info(BASIS_POINTS));
+
+        contract.cancel_queued(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: The timelock delay should be at least one epoch")]
+    fn test_set_timelock_with_zero_delay() {
+        let context = get_context(governance());
+        testing_env!(context);
+        let mut contract = registry();
+
+        contract.set_timelock(0, guardian());
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: The timelock delay should be at most 60 epochs")]
+    fn test_set_timelock_above_max_delay() {
+        let context = get_context(governance());
+        testing_env!(context);
+        let mut contract = registry();
+
+        contract.set_timelock(MAX_TIMELOCK_EPOCHS + 1, guardian());
+    }
+
+    #[test]
+    fn test_timelock_queues_council_proposals() {
+        let mut context = get_context(deployer());
+        let mut contract = timelocked_registry(&mut context);
+        contract.enable_council(vec![dave(), erin(), frank()], 2, 2);
+        assert_eq!(contract.get_council(), None);
+        context.epoch_height = 2;
+        testing_env!(context.clone());
+        contract.execute_queued(0);
+
+        set_caller(&mut context, dave());
+        let proposal_id = contract.propose(RegistryAction::AddValidator {
+            validator: alice(),
+            info: validator_info(BASIS_POINTS),
+        });
+        set_caller(&mut context, erin());
+        assert!(contract.confirm(proposal_id));
+        assert_eq!(contract.get_validator(alice()), None);
+        assert_eq!(contract.get_queued_change(1).unwrap().effective_epoch, 4);
+
+        context.epoch_height = 4;
+        testing_env!(context.clone());
+        contract.execute_queued(1);
+        assert!(contract.get_validator(alice()).is_some());
+    }
+
+    #[test]
+    fn test_migrate_v1_state() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+
+        let contract = registry();
+        // State written by the v1 layout, before the version was tracked.
+        let mut ratios = UnorderedMap::new(b"a".to_vec());
+        ratios.insert(&alice(), &10u32);
+        ratios.insert(&bob(), &20u32);
+        env::state_write(&RegistryV1 {
+            governance: governance(),
+            validator_info: ratios,
+            validator_count: 2,
+        });
+        env::storage_remove(b"STATE_VERSION");
+        assert_eq!(contract.get_state_version(), 1);
+
+        context.predecessor_account_id = governance();
+        testing_env!(context.clone());
+        let migrated = Registry::migrate();
+        assert_eq!(migrated.get_state_version(), STATE_VERSION);
+        assert_eq!(migrated.governance, governance());
+        assert_eq!(migrated.validator_count, 2);
+        assert_eq!(
+            migrated.get_validators(),
+            vec![
+                (alice(), ValidatorInfo::from_ratio(&alice(), 3_333)),
+                (bob(), ValidatorInfo::from_ratio(&bob(), 6_667)),
+            ]
+        );
+        assert_eq!(migrated.get_validator(bob()).unwrap().status, ValidatorStatus::Active);
+        let ratios: UnorderedMap<AccountId, u32> = UnorderedMap::new(b"a".to_vec());
+        assert_eq!(ratios.len(), 0);
+        assert_eq!(migrated.get_council(), None);
+        assert!(migrated.get_proposals(0, 10).is_empty());
+        let timelock = Timelock {
+            delay: DEFAULT_TIMELOCK_EPOCHS,
+            guardian: governance(),
+        };
+        assert_eq!(migrated.get_timelock(), Some(timelock));
+        assert!(migrated.get_queued_changes(0, 10).is_empty());
+        assert_eq!(migrated.get_approved_code_hash(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: Method migrate is private")]
+    fn test_migrate_with_non_self_call() {
+        let context = get_context(deployer());
+        testing_env!(context);
+
+        let contract = registry();
+        env::state_write(&contract);
+        Registry::migrate();
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: Caller is not Governance")]
+    fn test_upgrade_with_non_governance_call() {
+        let context = get_context(deployer());
+        testing_env!(context);
+
+        let mut contract = registry();
+        contract.upgrade(Base64VecU8(vec![0u8; 32]));
+    }
+
+    #[test]
+    fn test_upgrade_deploys_approved_code() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+        let mut contract = registry();
+        set_caller(&mut context, governance());
+
+        let code = vec![1u8; 8];
+        contract.upgrade(Base64VecU8(env::sha256(&code)));
+        assert_eq!(contract.get_approved_code_hash(), Some(Base64VecU8(env::sha256(&code))));
+
+        // Anyone can deploy the approved code, once.
+        set_caller(&mut context, bob());
+        contract.deploy_upgrade(Base64VecU8(code));
+        assert_eq!(contract.get_approved_code_hash(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: The code is not approved")]
+    fn test_deploy_upgrade_with_other_code() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+        let mut contract = registry();
+        set_caller(&mut context, governance());
+
+        contract.upgrade(Base64VecU8(env::sha256(&[1u8; 8])));
+        contract.deploy_upgrade(Base64VecU8(vec![2u8; 8]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: The code hash should be 32 bytes")]
+    fn test_upgrade_with_invalid_code_hash() {
+        let mut context = get_context(deployer());
+        testing_env!(context.clone());
+        let mut contract = registry();
+        set_caller(&mut context, governance());
+
+        contract.upgrade(Base64VecU8(vec![0u8; 8]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Registry: The Registry is governed by the council")]
+    fn test_upgrade_in_council_mode() {
+        let mut context = get_context(deployer());
+        let mut contract = council_registry(&mut context);
+
+        contract.upgrade(Base64VecU8(vec![0u8; 32]));
+    }
+
+    #[test]
+    fn test_council_approves_upgrade() {
+        let mut context = get_context(deployer());
+        let mut contract = council_registry(&mut context);
+
+        let code = vec![1u8; 8];
+        set_caller(&mut context, dave());
+        let proposal_id = contract.propose(RegistryAction::Upgrade {
+            code_hash: Base64VecU8(env::sha256(&code)),
+        });
+        assert_eq!(contract.get_approved_code_hash(), None);
+        set_caller(&mut context, erin());
+        assert!(contract.confirm(proposal_id));
+        assert_eq!(contract.get_approved_code_hash(), Some(Base64VecU8(env::sha256(&code))));
+        contract.deploy_upgrade(Base64VecU8(code));
+    }
+
     // #[test]
     // #[should_panic]
     // fn test_initialize_new_token_twice_fails() {
@@ -0,0 +1,133 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId, EpochHeight};
+
+use crate::{Registry, RegistryAction, RegistryError};
+
+/// Delay of a new or migrated Registry, about a day. Governance is the guardian.
+pub const DEFAULT_TIMELOCK_EPOCHS: EpochHeight = 2;
+
+/// Longest delay `set_timelock` accepts, about a month.
+pub const MAX_TIMELOCK_EPOCHS: EpochHeight = 60;
+
+/// Delay of Registry changes. Every change is queued and takes effect `delay` epochs later,
+/// unless the guardian cancels it.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Timelock {
+    /// Number of epochs between queueing and executing a change.
+    pub delay: EpochHeight,
+    /// Can cancel queued changes.
+    pub guardian: AccountId,
+}
+
+impl Timelock {
+    pub(crate) fn with_default_delay(guardian: AccountId) -> Self {
+        Self {
+            delay: DEFAULT_TIMELOCK_EPOCHS,
+            guardian,
+        }
+    }
+}
+
+/// Change of the Registry waiting for the timelock.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct QueuedChange {
+    pub action: RegistryAction,
+    pub queued_at: EpochHeight,
+    /// Epoch height from which the change can be executed.
+    pub effective_epoch: EpochHeight,
+}
+
+#[near_bindgen]
+impl Registry {
+    /// Delays every later change by `delay` epochs, at most `MAX_TIMELOCK_EPOCHS`, and lets
+    /// `guardian` cancel them. Only Governance can set the timelock, the change is queued
+    /// behind the current delay.
+    pub fn set_timelock(&mut self, delay: EpochHeight, guardian: AccountId) {
+        self.assert_governance();
+        self.dispatch_action(RegistryAction::SetTimelock { delay, guardian });
+    }
+
+    /// Executes a queued change once its effective epoch is reached. Anyone can call it.
+    /// An executed upgrade approves its code for `deploy_upgrade`.
+    pub fn execute_queued(&mut self, queued_id: u64) {
+        let change = self
+            .queued_changes
+            .get(&queued_id)
+            .unwrap_or_else(|| RegistryError::QueuedChangeNotFound(queued_id).panic());
+        if env::epoch_height() < change.effective_epoch {
+            RegistryError::TimelockNotReady(queued_id, change.effective_epoch).panic();
+        }
+        self.queued_changes.remove(&queued_id);
+        self.execute_action(change.action);
+    }
+
+    /// Drops a queued change. Only the guardian can cancel.
+    pub fn cancel_queued(&mut self, queued_id: u64) {
+        let guardian = self.timelock.as_ref().map(|timelock| &timelock.guardian);
+        if guardian != Some(&env::predecessor_account_id()) {
+            RegistryError::NotGuardian.panic();
+        }
+        if self.queued_changes.remove(&queued_id).is_none() {
+            RegistryError::QueuedChangeNotFound(queued_id).panic();
+        }
+    }
+
+    pub fn get_timelock(&self) -> Option<Timelock> {
+        self.timelock.clone()
+    }
+
+    pub fn get_queued_change(&self, queued_id: u64) -> Option<QueuedChange> {
+        self.queued_changes.get(&queued_id)
+    }
+
+    /// Returns up to `limit` queued changes with their ids, starting at `from_index`.
+    pub fn get_queued_changes(&self, from_index: u64, limit: u64) -> Vec<(u64, QueuedChange)> {
+        let queued_ids = self.queued_changes.keys_as_vector();
+        let changes = self.queued_changes.values_as_vector();
+        let end = std::cmp::min(from_index.saturating_add(limit), queued_ids.len());
+        (from_index..end)
+            .map(|index| (queued_ids.get(index).unwrap(), changes.get(index).unwrap()))
+            .collect()
+    }
+}
+
+impl Registry {
+    /// Queues `action` if there is a timelock and executes it otherwise.
+    pub(crate) fn dispatch_action(&mut self, action: RegistryAction) {
+        match self.timelock.as_ref().map(|timelock| timelock.delay) {
+            Some(delay) => self.queue_action(action, delay),
+            None => self.execute_action(action),
+        }
+    }
+
+    fn queue_action(&mut self, action: RegistryAction, delay: EpochHeight) {
+        let queued_id = self.next_queued_id;
+        self.next_queued_id += 1;
+        let queued_at = env::epoch_height();
+        let effective_epoch = queued_at
+            .checked_add(delay)
+            .unwrap_or_else(|| RegistryError::EffectiveEpochOverflow.panic());
+        let change = QueuedChange {
+            action,
+            queued_at,
+            effective_epoch,
+        };
+        self.queued_changes.insert(&queued_id, &change);
+    }
+
+    pub(crate) fn internal_set_timelock(&mut self, delay: EpochHeight, guardian: AccountId) {
+        if delay == 0 {
+            RegistryError::InvalidTimelockDelay.panic();
+        }
+        if delay > MAX_TIMELOCK_EPOCHS {
+            RegistryError::TimelockDelayTooLong(MAX_TIMELOCK_EPOCHS).panic();
+        }
+        if !env::is_valid_account_id(guardian.as_bytes()) {
+            RegistryError::InvalidGuardianAccountId.panic();
+        }
+        self.timelock = Some(Timelock { delay, guardian });
+    }
+}
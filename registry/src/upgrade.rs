@@ -0,0 +1,162 @@
+//! State versioning and self-upgrade.
+//!
+//! The layout version is kept under its own storage key, so the contract state stays a
+//! plain struct. A missing key means version 1, the layout deployed before versioning.
+//! Layouts of deployed versions are frozen here and converted by `migrate`.
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedMap;
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{env, near_bindgen, AccountId, Gas, Promise};
+
+use crate::weights::split;
+use crate::{
+    Registry, RegistryAction, RegistryError, StorageKey, Timelock, ValidatorInfo, BASIS_POINTS,
+};
+
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+
+/// Version of the current `Registry` layout.
+pub const STATE_VERSION: u32 = 2;
+
+/// Gas attached to the `migrate` call of `deploy_upgrade`.
+const GAS_FOR_MIGRATE: Gas = 100_000_000_000_000;
+
+/// `Registry` layout version 1.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct RegistryV1 {
+    pub governance: AccountId,
+    /// Validator AccountID -> Ratio, under the prefix b"a".
+    pub validator_info: UnorderedMap<AccountId, u32>,
+    pub validator_count: u32,
+}
+
+impl From<RegistryV1> for Registry {
+    fn from(mut state: RegistryV1) -> Self {
+        // Validators get a record each, moved under a new prefix. The v1 ratios are scaled
+        // to basis points, the ratios of active validators sum to 10000.
+        let mut validator_info = UnorderedMap::new(StorageKey::Validators.into_prefix());
+        let mut active = Vec::new();
+        for (validator, ratio) in state.validator_info.iter() {
+            let info = ValidatorInfo::from_ratio(&validator, ratio);
+            validator_info.insert(&validator, &info);
+            if info.is_active() {
+                active.push((validator, info));
+            }
+        }
+        state.validator_info.clear();
+        let ratios: Vec<u128> = active.iter().map(|(_, info)| info.ratio.into()).collect();
+        let weights = split(BASIS_POINTS.into(), &ratios);
+        for ((validator, mut info), weight) in active.into_iter().zip(weights) {
+            info.ratio = weight as u32;
+            validator_info.insert(&validator, &info);
+        }
+        // There is no council or approved upgrade in v1. Changes are delayed from now on,
+        // Governance guards them until it sets its own timelock.
+        Self {
+            governance: state.governance.clone(),
+            validator_info,
+            validator_count: state.validator_count,
+            council: None,
+            proposals: UnorderedMap::new(StorageKey::Proposals.into_prefix()),
+            next_proposal_id: 0,
+            timelock: Some(Timelock::with_default_delay(state.governance)),
+            queued_changes: UnorderedMap::new(StorageKey::QueuedChanges.into_prefix()),
+            next_queued_id: 0,
+            approved_code_hash: None,
+        }
+    }
+}
+
+/// State of any deployed version.
+pub enum VersionedRegistry {
+    V1(RegistryV1),
+    Current(Registry),
+}
+
+impl VersionedRegistry {
+    fn read() -> Self {
+        match read_state_version() {
+            1 => VersionedRegistry::V1(
+                env::state_read().unwrap_or_else(|| RegistryError::NotInitialized.panic()),
+            ),
+            STATE_VERSION => VersionedRegistry::Current(
+                env::state_read().unwrap_or_else(|| RegistryError::NotInitialized.panic()),
+            ),
+            version => RegistryError::UnsupportedStateVersion(version).panic(),
+        }
+    }
+}
+
+impl From<VersionedRegistry> for Registry {
+    fn from(state: VersionedRegistry) -> Self {
+        match state {
+            VersionedRegistry::V1(state) => state.into(),
+            VersionedRegistry::Current(state) => state,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Registry {
+    /// Converts the state written by any deployed version to the current layout.
+    /// Called by `deploy_upgrade` right after the new code is deployed.
+    #[init]
+    pub fn migrate() -> Self {
+        if env::predecessor_account_id() != env::current_account_id() {
+            RegistryError::PrivateMethod("migrate").panic();
+        }
+        let state = VersionedRegistry::read().into();
+        write_state_version();
+        state
+    }
+
+    /// Approves the code with the sha256 `code_hash` for `deploy_upgrade`. Only Governance can
+    /// upgrade, the approval is proposed to the council and queued by the timelock like any
+    /// other change.
+    pub fn upgrade(&mut self, code_hash: Base64VecU8) {
+        self.assert_governance();
+        self.dispatch_action(RegistryAction::Upgrade { code_hash });
+    }
+
+    /// Deploys the approved `code` to this account and calls `migrate` on it. Anyone can
+    /// deploy, an approval is used once.
+    pub fn deploy_upgrade(&mut self, code: Base64VecU8) -> Promise {
+        let code_hash = env::sha256(&code.0);
+        if self.approved_code_hash.as_ref() != Some(&code_hash) {
+            RegistryError::UpgradeNotApproved.panic();
+        }
+        self.approved_code_hash = None;
+        Promise::new(env::current_account_id())
+            .deploy_contract(code.0)
+            .function_call(b"migrate".to_vec(), vec![], 0, GAS_FOR_MIGRATE)
+    }
+
+    pub fn get_approved_code_hash(&self) -> Option<Base64VecU8> {
+        self.approved_code_hash.clone().map(Base64VecU8)
+    }
+
+    pub fn get_state_version(&self) -> u32 {
+        read_state_version()
+    }
+}
+
+impl Registry {
+    pub(crate) fn internal_approve_upgrade(&mut self, code_hash: Vec<u8>) {
+        if code_hash.len() != 32 {
+            RegistryError::InvalidCodeHash.panic();
+        }
+        self.approved_code_hash = Some(code_hash);
+    }
+}
+
+pub(crate) fn read_state_version() -> u32 {
+    env::storage_read(STATE_VERSION_KEY)
+        .map(|value| {
+            u32::try_from_slice(&value).unwrap_or_else(|_| RegistryError::InvalidStateVersion.panic())
+        })
+        .unwrap_or(1)
+}
+
+pub(crate) fn write_state_version() {
+    env::storage_write(STATE_VERSION_KEY, &STATE_VERSION.try_to_vec().unwrap());
+}
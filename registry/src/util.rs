@@ -1,2 +1,16 @@
+use uint::construct_uint;
+
 // Near supported 1e24
-pub const DECIMAL: u128 = 1_000_000_000_000_000_000_000_000;
\ No newline at end of file
+pub const DECIMAL: u128 = 1_000_000_000_000_000_000_000_000;
+
+construct_uint! {
+    /// 256-bit unsigned integer.
+    pub struct U256(4);
+}
+
+/// Returns the quotient and the remainder of `a * b / c`, multiplied in 256 bits so it
+/// can't overflow. The quotient must fit in 128 bits.
+pub fn mul_div_rem(a: u128, b: u128, c: u128) -> (u128, u128) {
+    let (quotient, remainder) = (U256::from(a) * U256::from(b)).div_mod(U256::from(c));
+    (quotient.as_u128(), remainder.as_u128())
+}
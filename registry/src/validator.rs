@@ -0,0 +1,67 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, AccountId, EpochHeight};
+
+use crate::RegistryError;
+
+/// Commission and weights are given in basis points.
+pub const BASIS_POINTS: u32 = 10_000;
+
+/// Lifecycle of a registered validator.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum ValidatorStatus {
+    /// Receives new stake.
+    Active,
+    /// Keeps its stake but receives no new stake.
+    Paused,
+    /// Its stake is being withdrawn.
+    Draining,
+    /// Kept for the record, holds no stake.
+    Removed,
+}
+
+/// Record of a registered validator.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ValidatorInfo {
+    /// Staking pool the stake is delegated to.
+    pub pool_account_id: AccountId,
+    /// Weight in basis points, see `set_weights`.
+    pub ratio: u32,
+    pub status: ValidatorStatus,
+    /// Commission of the pool in basis points.
+    pub commission: u32,
+    pub display_name: String,
+    /// Epoch height the validator was added at. Set by the Registry, a given value is ignored.
+    #[serde(default)]
+    pub added_at: EpochHeight,
+}
+
+impl ValidatorInfo {
+    /// Record of a validator registered before v2, which only had a ratio.
+    pub(crate) fn from_ratio(validator: &AccountId, ratio: u32) -> Self {
+        Self {
+            pool_account_id: validator.clone(),
+            ratio,
+            status: ValidatorStatus::Active,
+            commission: 0,
+            display_name: String::new(),
+            added_at: 0,
+        }
+    }
+
+    /// Only active validators have a weight.
+    pub fn is_active(&self) -> bool {
+        self.status == ValidatorStatus::Active
+    }
+
+    pub(crate) fn assert_valid(&self) {
+        if !env::is_valid_account_id(self.pool_account_id.as_bytes()) {
+            RegistryError::InvalidPoolAccountId.panic();
+        }
+        if self.commission > BASIS_POINTS {
+            RegistryError::InvalidCommission(self.commission).panic();
+        }
+    }
+}
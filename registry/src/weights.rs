@@ -0,0 +1,100 @@
+use near_sdk::{near_bindgen, AccountId};
+
+use crate::util::mul_div_rem;
+use crate::{Registry, RegistryAction, RegistryError, ValidatorInfo, BASIS_POINTS};
+
+/// Ratios are weights in basis points. The weights of active validators sum to
+/// `BASIS_POINTS`, or to zero before any weight is set. Every change is checked against it.
+#[near_bindgen]
+impl Registry {
+    /// Sets the weights of several validators at once. Only Governance can set weights.
+    /// Panics unless the weights of active validators sum to 10000 afterwards.
+    pub fn set_weights(&mut self, weights: Vec<(AccountId, u32)>) {
+        self.assert_governance();
+        self.dispatch_action(RegistryAction::SetWeights { weights });
+    }
+
+    /// Returns the weights of active validators in basis points.
+    pub fn get_normalized_weights(&self) -> Vec<(AccountId, u32)> {
+        self.validator_info
+            .iter()
+            .filter(|(_, info)| info.is_active())
+            .map(|(validator, info)| (validator, info.ratio))
+            .collect()
+    }
+}
+
+impl Registry {
+    pub(crate) fn internal_set_weights(&mut self, weights: Vec<(AccountId, u32)>) {
+        for (index, (validator, ratio)) in weights.iter().enumerate() {
+            if weights[..index].iter().any(|(account_id, _)| account_id == validator) {
+                RegistryError::DuplicateValidator(validator.clone()).panic();
+            }
+            let mut info = self
+                .validator_info
+                .get(validator)
+                .unwrap_or_else(|| RegistryError::ValidatorNotFound.panic());
+            info.ratio = *ratio;
+            self.validator_info.insert(validator, &info);
+        }
+        self.assert_weights();
+    }
+
+    /// Splits `weight` of a validator that is no longer active over the active validators
+    /// in proportion to their weights, so they keep summing to `BASIS_POINTS`.
+    pub(crate) fn redistribute_weight(&mut self, weight: u32) {
+        let validators = self.get_normalized_weights();
+        let weights: Vec<u128> = validators.iter().map(|(_, ratio)| (*ratio).into()).collect();
+        let parts = split(weight.into(), &weights);
+        for ((validator, ratio), part) in validators.into_iter().zip(parts) {
+            if part > 0 {
+                let mut info = self.validator_info.get(&validator).unwrap();
+                info.ratio = ratio + part as u32;
+                self.validator_info.insert(&validator, &info);
+            }
+        }
+    }
+
+    /// Panics unless the weights of active validators sum to `BASIS_POINTS` or to zero.
+    pub(crate) fn assert_weights(&self) {
+        let total = self.total_weight();
+        if total != 0 && total != u64::from(BASIS_POINTS) {
+            RegistryError::WeightsSumMismatch(total).panic();
+        }
+    }
+
+    fn total_weight(&self) -> u64 {
+        self.validator_info
+            .values()
+            .filter(ValidatorInfo::is_active)
+            .map(|info| u64::from(info.ratio))
+            .sum()
+    }
+}
+
+/// Splits `total` in proportion to `weights` with the largest remainder method. The remainder
+/// left by rounding down goes one unit at a time to the largest fractions, ties go to the
+/// lower index. Returns zeros if all weights are zero.
+pub(crate) fn split(total: u128, weights: &[u128]) -> Vec<u128> {
+    let weight_sum: u128 = weights.iter().sum();
+    if weight_sum == 0 {
+        return vec![0; weights.len()];
+    }
+    let mut parts = Vec::with_capacity(weights.len());
+    let mut fractions = Vec::with_capacity(weights.len());
+    for (index, weight) in weights.iter().enumerate() {
+        let (part, fraction) = mul_div_rem(total, *weight, weight_sum);
+        parts.push(part);
+        fractions.push((fraction, index));
+    }
+    let mut left = total - parts.iter().sum::<u128>();
+    fractions.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+    for (_, index) in fractions {
+        if left == 0 {
+            break;
+        }
+        parts[index] += 1;
+        left -= 1;
+    }
+    parts
+}
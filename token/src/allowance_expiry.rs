@@ -0,0 +1,106 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::events::{Approve, Event};
+use crate::{escrow_key, ScaleToken};
+
+/// Point after which an allowance is treated as zero.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum AllowanceExpiry {
+    /// Block timestamp in nanoseconds.
+    Timestamp(U64),
+    /// Epoch height.
+    EpochHeight(U64),
+}
+
+impl AllowanceExpiry {
+    pub fn is_expired(&self) -> bool {
+        match self {
+            AllowanceExpiry::Timestamp(timestamp) => env::block_timestamp() >= timestamp.0,
+            AllowanceExpiry::EpochHeight(epoch_height) => env::epoch_height() >= epoch_height.0,
+        }
+    }
+}
+
+#[near_bindgen]
+impl ScaleToken {
+    /// Returns the expiry of the allowance of `escrow_account_id`, `None` if it never expires.
+    pub fn get_allowance_expiry(
+        &self,
+        owner_id: AccountId,
+        escrow_account_id: AccountId,
+    ) -> Option<AllowanceExpiry> {
+        self.internal_allowance_expiry(&owner_id, &escrow_account_id)
+    }
+
+    /// Removes the expired allowances granted by `owner_id` to `escrow_account_ids`.
+    /// Anyone can call it, the freed storage is credited to the owner.
+    /// Returns the number of removed allowances.
+    pub fn remove_expired_allowances(
+        &mut self,
+        owner_id: AccountId,
+        escrow_account_ids: Vec<AccountId>,
+    ) -> u32 {
+        let initial_storage = env::storage_usage();
+        let mut account = self.get_account(&owner_id);
+        let allowance_storage = env::storage_usage();
+        let mut removed = 0;
+        for escrow_account_id in escrow_account_ids.iter() {
+            if self.is_allowance_expired(&owner_id, escrow_account_id) {
+                account.set_allowance(escrow_account_id, 0);
+                self.set_allowance_expiry(&owner_id, escrow_account_id, None);
+                Approve {
+                    owner_id: &owner_id,
+                    escrow_account_id,
+                    amount: 0.into(),
+                    expires_at: None,
+                }
+                .emit();
+                removed += 1;
+            }
+        }
+        if removed > 0 {
+            self.track_allowance_storage(&owner_id, allowance_storage);
+            self.set_account(&owner_id, &mut account);
+            self.settle_storage(&env::predecessor_account_id(), initial_storage);
+        }
+        removed
+    }
+}
+
+impl ScaleToken {
+    pub(crate) fn internal_allowance_expiry(
+        &self,
+        owner_id: &AccountId,
+        escrow_account_id: &AccountId,
+    ) -> Option<AllowanceExpiry> {
+        self.allowance_expiries
+            .get(&escrow_key(owner_id, escrow_account_id))
+    }
+
+    /// Returns whether the allowance of `escrow_account_id` has expired.
+    pub(crate) fn is_allowance_expired(
+        &self,
+        owner_id: &AccountId,
+        escrow_account_id: &AccountId,
+    ) -> bool {
+        self.internal_allowance_expiry(owner_id, escrow_account_id)
+            .map_or(false, |expiry| expiry.is_expired())
+    }
+
+    pub(crate) fn set_allowance_expiry(
+        &mut self,
+        owner_id: &AccountId,
+        escrow_account_id: &AccountId,
+        expires_at: Option<AllowanceExpiry>,
+    ) {
+        let key = escrow_key(owner_id, escrow_account_id);
+        match expires_at {
+            Some(expires_at) => self.allowance_expiries.insert(&key, &expires_at),
+            None => self.allowance_expiries.remove(&key),
+        };
+    }
+}
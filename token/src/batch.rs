@@ -0,0 +1,92 @@
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId, Balance, Gas};
+
+use crate::events::{Event, FtTransfer};
+use crate::{ScaleToken, TokenError};
+
+/// Gas used by `batch_transfer` besides the receivers.
+const GAS_FOR_BATCH_TRANSFER: Gas = 10_000_000_000_000;
+
+/// Gas used by `batch_transfer` per receiver.
+const GAS_PER_BATCH_RECEIVER: Gas = 5_000_000_000_000;
+
+#[near_bindgen]
+impl ScaleToken {
+    /// Transfers tokens from the caller to every receiver of `transfers`.
+    /// Storage of new receivers is paid from the prepaid storage balance of the caller.
+    /// The number of receivers is limited by the prepaid gas.
+    #[payable]
+    pub fn batch_transfer(&mut self, transfers: Vec<(AccountId, U128)>, memo: Option<String>) {
+        self.assert_not_paused();
+        if transfers.is_empty() {
+            TokenError::EmptyBatch.panic();
+        }
+        let max_len = max_batch_transfer_len();
+        if transfers.len() as u64 > max_len {
+            TokenError::BatchTooLong(max_len).panic();
+        }
+        let owner_id = env::predecessor_account_id();
+
+        // Validating all receivers before touching the state.
+        let mut total_shares: Balance = 0;
+        let mut receivers = Vec::with_capacity(transfers.len());
+        for (new_owner_id, amount) in transfers.iter() {
+            if !env::is_valid_account_id(new_owner_id.as_bytes()) {
+                TokenError::InvalidNewOwnerAccountId.panic();
+            }
+            if amount.0 == 0 {
+                TokenError::ZeroTransfer.panic();
+            }
+            if *new_owner_id == owner_id {
+                TokenError::SelfTransfer.panic();
+            }
+            if transfers[..receivers.len()]
+                .iter()
+                .any(|(account_id, _)| account_id == new_owner_id)
+            {
+                TokenError::DuplicateReceiver(new_owner_id.clone()).panic();
+            }
+            let shares = self.amount_to_shares(amount.0);
+            if shares == 0 {
+                TokenError::TransferTooSmall.panic();
+            }
+            total_shares = total_shares
+                .checked_add(shares)
+                .unwrap_or_else(|| TokenError::NotEnoughBalance.panic());
+            receivers.push(shares);
+        }
+
+        self.deposit_attached_storage();
+        let initial_storage = env::storage_usage();
+        let mut account = self.get_account(&owner_id);
+        self.assert_unlocked_shares(&owner_id, &account, total_shares);
+        account.shares -= total_shares;
+        self.set_account(&owner_id, &mut account);
+
+        for ((new_owner_id, _), shares) in transfers.iter().zip(receivers) {
+            let mut new_account = self.get_account(new_owner_id);
+            new_account.shares = new_account
+                .shares
+                .checked_add(shares)
+                .unwrap_or_else(|| TokenError::BalanceOverflow.panic());
+            self.set_account(new_owner_id, &mut new_account);
+        }
+        self.settle_storage(&owner_id, initial_storage);
+
+        let events: Vec<FtTransfer> = transfers
+            .iter()
+            .map(|(new_owner_id, amount)| FtTransfer {
+                old_owner_id: &owner_id,
+                new_owner_id,
+                amount: amount.0.into(),
+                memo: memo.as_deref(),
+            })
+            .collect();
+        FtTransfer::emit_many(&events);
+    }
+}
+
+/// Returns the number of receivers `batch_transfer` can handle with the prepaid gas.
+fn max_batch_transfer_len() -> u64 {
+    env::prepaid_gas().saturating_sub(GAS_FOR_BATCH_TRANSFER) / GAS_PER_BATCH_RECEIVER
+}
@@ -0,0 +1,160 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::Vector;
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId, Balance, EpochHeight};
+
+use crate::util::mul_div;
+use crate::{ScaleToken, StorageKey, TokenError};
+
+/// A value recorded at the epoch it last changed in.
+pub trait Checkpoint: BorshSerialize + BorshDeserialize {
+    fn epoch_height(&self) -> EpochHeight;
+    fn same_value(&self, other: &Self) -> bool;
+    fn is_empty(&self) -> bool;
+}
+
+/// Shares of one account.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccountCheckpoint {
+    pub epoch_height: EpochHeight,
+    pub shares: Balance,
+}
+
+impl Checkpoint for AccountCheckpoint {
+    fn epoch_height(&self) -> EpochHeight {
+        self.epoch_height
+    }
+
+    fn same_value(&self, other: &Self) -> bool {
+        self.shares == other.shares
+    }
+
+    fn is_empty(&self) -> bool {
+        self.shares == 0
+    }
+}
+
+/// Total supply and total shares of the token.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct SupplyCheckpoint {
+    pub epoch_height: EpochHeight,
+    pub total_supply: Balance,
+    pub total_shares: Balance,
+}
+
+impl Checkpoint for SupplyCheckpoint {
+    fn epoch_height(&self) -> EpochHeight {
+        self.epoch_height
+    }
+
+    fn same_value(&self, other: &Self) -> bool {
+        self.total_supply == other.total_supply && self.total_shares == other.total_shares
+    }
+
+    fn is_empty(&self) -> bool {
+        self.total_supply == 0 && self.total_shares == 0
+    }
+}
+
+/// Pushes `checkpoint` if the value changed in a new epoch,
+/// or overwrites the last one if it changed again in the same epoch.
+/// Returns whether `checkpoints` was modified.
+fn record<C: Checkpoint>(checkpoints: &mut Vector<C>, checkpoint: C) -> bool {
+    let len = checkpoints.len();
+    let last = if len == 0 {
+        None
+    } else {
+        checkpoints.get(len - 1)
+    };
+    match last {
+        Some(last) if last.same_value(&checkpoint) => false,
+        Some(last) if last.epoch_height() == checkpoint.epoch_height() => {
+            checkpoints.replace(len - 1, &checkpoint);
+            true
+        }
+        None if checkpoint.is_empty() => false,
+        _ => {
+            checkpoints.push(&checkpoint);
+            true
+        }
+    }
+}
+
+/// Binary search for the last checkpoint recorded at or before `epoch_height`.
+fn find<C: Checkpoint>(checkpoints: &Vector<C>, epoch_height: EpochHeight) -> Option<C> {
+    let mut low = 0;
+    let mut high = checkpoints.len();
+    while low < high {
+        let mid = (low + high) / 2;
+        if checkpoints.get(mid).unwrap().epoch_height() <= epoch_height {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    if low == 0 {
+        None
+    } else {
+        checkpoints.get(low - 1)
+    }
+}
+
+#[near_bindgen]
+impl ScaleToken {
+    /// Returns balance of the `owner_id` account at the end of `epoch`.
+    pub fn get_balance_at(&self, owner_id: AccountId, epoch: EpochHeight) -> U128 {
+        let account_hash = env::sha256(owner_id.as_bytes());
+        let shares = self
+            .account_checkpoints
+            .get(&account_hash)
+            .and_then(|checkpoints| find(&checkpoints, epoch))
+            .map_or(0, |checkpoint| checkpoint.shares);
+        match find(&self.supply_checkpoints, epoch) {
+            Some(supply) if supply.total_shares > 0 => {
+                mul_div(shares, supply.total_supply, supply.total_shares)
+                    .unwrap_or_else(|| TokenError::ConversionOverflow.panic())
+                    .into()
+            }
+            _ => 0.into(),
+        }
+    }
+
+    /// Returns total supply of tokens at the end of `epoch`.
+    pub fn get_total_supply_at(&self, epoch: EpochHeight) -> U128 {
+        find(&self.supply_checkpoints, epoch)
+            .map_or(0, |supply| supply.total_supply)
+            .into()
+    }
+}
+
+impl ScaleToken {
+    pub(crate) fn record_account_checkpoint(&mut self, account_hash: Vec<u8>, shares: Balance) {
+        let mut checkpoints = self
+            .account_checkpoints
+            .get(&account_hash)
+            .unwrap_or_else(|| {
+                Vector::new(
+                    StorageKey::AccountCheckpointsEntry {
+                        account_hash: account_hash.clone(),
+                    }
+                    .into_prefix(),
+                )
+            });
+        let checkpoint = AccountCheckpoint {
+            epoch_height: env::epoch_height(),
+            shares,
+        };
+        if record(&mut checkpoints, checkpoint) {
+            self.account_checkpoints.insert(&account_hash, &checkpoints);
+        }
+    }
+
+    pub(crate) fn record_supply_checkpoint(&mut self) {
+        let checkpoint = SupplyCheckpoint {
+            epoch_height: env::epoch_height(),
+            total_supply: self.total_supply,
+            total_shares: self.total_shares,
+        };
+        record(&mut self.supply_checkpoints, checkpoint);
+    }
+}
@@ -0,0 +1,299 @@
+//! Errors of the ScaleToken. A panic message reads `<message> (E<code>)`, codes are stable
+//! while messages may be reworded.
+use std::fmt;
+
+use near_sdk::{env, AccountId, Balance};
+
+use crate::Role;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenError {
+    // State and upgrades.
+    AlreadyInitialized,
+    NotInitialized,
+    UnsupportedStateVersion(u32),
+    InvalidStateVersion,
+    PrivateMethod(&'static str),
+
+    // Access control and emergency stop.
+    MissingRole(Role),
+    LastAdmin,
+    NotPendingTokenizer,
+    NoPendingTokenizer,
+    SameTokenizer,
+    Paused,
+    AlreadyPaused,
+    NotPaused,
+
+    // Accounts, transfers and allowances.
+    InvalidAccountId,
+    InvalidOwnerAccountId,
+    InvalidNewOwnerAccountId,
+    InvalidEscrowAccountId,
+    InvalidTokenizerAccountId,
+    ZeroTransfer,
+    SelfTransfer,
+    TransferTooSmall,
+    NotEnoughBalance,
+    BalanceOverflow,
+    EmptyBatch,
+    BatchTooLong(u64),
+    DuplicateReceiver(AccountId),
+    NotEnoughAllowance,
+    SelfAllowanceIncrement,
+    SelfAllowanceDecrement,
+    AllowanceExpiryInPast,
+    LegacyAllowancesLeft(u32),
+    RequiresOneYocto,
+    NotEnoughGas,
+    ZeroLock,
+    LockTooSmall,
+    NotEnoughUnlockedBalance,
+    NotEnoughLockedBalance,
+
+    // Supply.
+    ZeroMint,
+    MintTooSmall,
+    ZeroBurn,
+    TotalSupplyOverflow,
+    TotalSharesOverflow,
+    TotalSupplyUnderflow,
+    ZeroPooledNear,
+    PooledNearWithoutShares,
+    ConversionOverflow,
+
+    // Storage (NEP-145).
+    NotRegistered(AccountId),
+    DepositBelowMinimum(Balance),
+    InsufficientStorageBalance { required: Balance, balance: Balance },
+    WithdrawExceedsAvailable,
+    UnregisterWithAllowances,
+    UnregisterWithBalance,
+    UnregisterWithLockedBalance,
+    UnregisterWithStorageCredit,
+
+    // Metadata (NEP-148).
+    UnsupportedMetadataSpec,
+    EmptyMetadataName,
+    EmptyMetadataSymbol,
+    MetadataReferenceMismatch,
+    InvalidMetadataReferenceHash,
+    DecimalsChanged,
+}
+
+impl TokenError {
+    pub fn code(&self) -> u16 {
+        match self {
+            TokenError::AlreadyInitialized => 100,
+            TokenError::NotInitialized => 101,
+            TokenError::UnsupportedStateVersion(_) => 102,
+            TokenError::InvalidStateVersion => 103,
+            TokenError::PrivateMethod(_) => 104,
+
+            TokenError::MissingRole(_) => 200,
+            TokenError::LastAdmin => 201,
+            TokenError::NotPendingTokenizer => 202,
+            TokenError::NoPendingTokenizer => 203,
+            TokenError::SameTokenizer => 204,
+            TokenError::Paused => 205,
+            TokenError::AlreadyPaused => 206,
+            TokenError::NotPaused => 207,
+
+            TokenError::InvalidAccountId => 300,
+            TokenError::InvalidOwnerAccountId => 301,
+            TokenError::InvalidNewOwnerAccountId => 302,
+            TokenError::InvalidEscrowAccountId => 303,
+            TokenError::InvalidTokenizerAccountId => 304,
+            TokenError::ZeroTransfer => 310,
+            TokenError::SelfTransfer => 311,
+            TokenError::TransferTooSmall => 312,
+            TokenError::NotEnoughBalance => 313,
+            TokenError::BalanceOverflow => 314,
+            TokenError::EmptyBatch => 315,
+            TokenError::BatchTooLong(_) => 316,
+            TokenError::DuplicateReceiver(_) => 317,
+            TokenError::NotEnoughAllowance => 320,
+            TokenError::SelfAllowanceIncrement => 321,
+            TokenError::SelfAllowanceDecrement => 322,
+            TokenError::AllowanceExpiryInPast => 323,
+            TokenError::LegacyAllowancesLeft(_) => 324,
+            TokenError::RequiresOneYocto => 330,
+            TokenError::NotEnoughGas => 331,
+            TokenError::ZeroLock => 340,
+            TokenError::LockTooSmall => 341,
+            TokenError::NotEnoughUnlockedBalance => 342,
+            TokenError::NotEnoughLockedBalance => 343,
+
+            TokenError::ZeroMint => 400,
+            TokenError::MintTooSmall => 401,
+            TokenError::ZeroBurn => 402,
+            TokenError::TotalSupplyOverflow => 403,
+            TokenError::TotalSharesOverflow => 404,
+            TokenError::TotalSupplyUnderflow => 405,
+            TokenError::ZeroPooledNear => 406,
+            TokenError::PooledNearWithoutShares => 407,
+            TokenError::ConversionOverflow => 408,
+
+            TokenError::NotRegistered(_) => 500,
+            TokenError::DepositBelowMinimum(_) => 501,
+            TokenError::InsufficientStorageBalance { .. } => 502,
+            TokenError::WithdrawExceedsAvailable => 503,
+            TokenError::UnregisterWithAllowances => 504,
+            TokenError::UnregisterWithBalance => 505,
+            TokenError::UnregisterWithLockedBalance => 506,
+            TokenError::UnregisterWithStorageCredit => 507,
+
+            TokenError::UnsupportedMetadataSpec => 600,
+            TokenError::EmptyMetadataName => 601,
+            TokenError::EmptyMetadataSymbol => 602,
+            TokenError::MetadataReferenceMismatch => 603,
+            TokenError::InvalidMetadataReferenceHash => 604,
+            TokenError::DecimalsChanged => 605,
+        }
+    }
+
+    /// Extracts the code from a panic message of the contract.
+    pub fn parse_code(message: &str) -> Option<u16> {
+        let start = message.rfind("(E")? + 2;
+        let end = start + message[start..].find(')')?;
+        message[start..end].parse().ok()
+    }
+
+    pub(crate) fn panic(&self) -> ! {
+        env::panic(self.to_string().as_bytes())
+    }
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenError::AlreadyInitialized => write!(f, "Already initialized"),
+            TokenError::NotInitialized => write!(f, "ScaleToken should be initialized before usage"),
+            TokenError::UnsupportedStateVersion(version) => {
+                write!(f, "Unsupported state version {}", version)
+            }
+            TokenError::InvalidStateVersion => write!(f, "Cannot deserialize the state version"),
+            TokenError::PrivateMethod(method) => write!(f, "Method {} is private", method),
+
+            TokenError::MissingRole(role) => {
+                write!(f, "Caller does not have the {} role", role.as_str())
+            }
+            TokenError::LastAdmin => write!(f, "Can't remove the last admin"),
+            TokenError::NotPendingTokenizer => write!(f, "Caller is not the pending Tokenizer"),
+            TokenError::NoPendingTokenizer => write!(f, "No pending Tokenizer"),
+            TokenError::SameTokenizer => write!(
+                f,
+                "The new Tokenizer should be different from the current Tokenizer"
+            ),
+            TokenError::Paused => write!(f, "Token is paused"),
+            TokenError::AlreadyPaused => write!(f, "Token is already paused"),
+            TokenError::NotPaused => write!(f, "Token is not paused"),
+
+            TokenError::InvalidAccountId => write!(f, "Account ID is invalid"),
+            TokenError::InvalidOwnerAccountId => write!(f, "Owner's account ID is invalid"),
+            TokenError::InvalidNewOwnerAccountId => write!(f, "New owner's account ID is invalid"),
+            TokenError::InvalidEscrowAccountId => write!(f, "Escrow account ID is invalid"),
+            TokenError::InvalidTokenizerAccountId => write!(f, "Tokenizer account ID is invalid"),
+            TokenError::ZeroTransfer => write!(f, "Can't transfer 0 tokens"),
+            TokenError::SelfTransfer => write!(
+                f,
+                "The new owner should be different from the current owner"
+            ),
+            TokenError::TransferTooSmall => write!(f, "Amount is too small to transfer a share"),
+            TokenError::NotEnoughBalance => write!(f, "Not enough balance"),
+            TokenError::BalanceOverflow => write!(f, "Balance overflow"),
+            TokenError::EmptyBatch => write!(f, "The batch has no receivers"),
+            TokenError::BatchTooLong(max_len) => write!(
+                f,
+                "The batch has too many receivers for the prepaid gas, the maximum is {}",
+                max_len
+            ),
+            TokenError::DuplicateReceiver(account_id) => {
+                write!(f, "The receiver {} is listed more than once", account_id)
+            }
+            TokenError::NotEnoughAllowance => write!(f, "Not enough allowance"),
+            TokenError::SelfAllowanceIncrement => {
+                write!(f, "Can not increment allowance for yourself")
+            }
+            TokenError::SelfAllowanceDecrement => {
+                write!(f, "Can not decrement allowance for yourself")
+            }
+            TokenError::AllowanceExpiryInPast => write!(f, "Allowance expiry is in the past"),
+            TokenError::LegacyAllowancesLeft(count) => write!(
+                f,
+                "{} allowances granted before the upgrade are left, decrement them to zero first",
+                count
+            ),
+            TokenError::RequiresOneYocto => {
+                write!(f, "Requires attached deposit of exactly 1 yoctoNEAR")
+            }
+            TokenError::NotEnoughGas => write!(f, "More gas is required"),
+            TokenError::ZeroLock => write!(f, "Can't lock or unlock 0 tokens"),
+            TokenError::LockTooSmall => write!(f, "Amount is too small to lock a share"),
+            TokenError::NotEnoughUnlockedBalance => write!(f, "Not enough unlocked balance"),
+            TokenError::NotEnoughLockedBalance => write!(f, "Not enough locked balance"),
+
+            TokenError::ZeroMint => write!(f, "Can't mint 0 tokens"),
+            TokenError::MintTooSmall => write!(f, "Amount is too small to mint a share"),
+            TokenError::ZeroBurn => write!(f, "Can't burn 0 tokens"),
+            TokenError::TotalSupplyOverflow => write!(f, "Total supply overflow"),
+            TokenError::TotalSharesOverflow => write!(f, "Total shares overflow"),
+            TokenError::TotalSupplyUnderflow => write!(f, "Total supply underflow"),
+            TokenError::ZeroPooledNear => {
+                write!(f, "Can't sync zero pooled NEAR while there are shares")
+            }
+            TokenError::PooledNearWithoutShares => {
+                write!(f, "The pooled NEAR has no shares, sync it to zero before minting")
+            }
+            TokenError::ConversionOverflow => {
+                write!(f, "Converting between shares and pooled NEAR overflows u128")
+            }
+
+            TokenError::NotRegistered(account_id) => {
+                write!(f, "The account {} is not registered", account_id)
+            }
+            TokenError::DepositBelowMinimum(min_balance) => write!(
+                f,
+                "The attached deposit is less than the minimum storage balance {}",
+                min_balance
+            ),
+            TokenError::InsufficientStorageBalance { required, balance } => write!(
+                f,
+                "The required storage balance is {}, but the storage balance is {}",
+                required, balance
+            ),
+            TokenError::WithdrawExceedsAvailable => write!(
+                f,
+                "The amount is greater than the available storage balance"
+            ),
+            TokenError::UnregisterWithAllowances => write!(
+                f,
+                "Can't unregister the account with outstanding allowances"
+            ),
+            TokenError::UnregisterWithBalance => write!(
+                f,
+                "Can't unregister the account with the positive balance without force"
+            ),
+            TokenError::UnregisterWithLockedBalance => {
+                write!(f, "Can't unregister the account with locked tokens")
+            }
+            TokenError::UnregisterWithStorageCredit => write!(
+                f,
+                "Can't unregister the account while it pays for the storage of other accounts"
+            ),
+
+            TokenError::UnsupportedMetadataSpec => write!(f, "Unsupported metadata spec"),
+            TokenError::EmptyMetadataName => write!(f, "Metadata name is empty"),
+            TokenError::EmptyMetadataSymbol => write!(f, "Metadata symbol is empty"),
+            TokenError::MetadataReferenceMismatch => write!(
+                f,
+                "Metadata reference and reference_hash should be set together"
+            ),
+            TokenError::InvalidMetadataReferenceHash => {
+                write!(f, "Metadata reference_hash has to be 32 bytes")
+            }
+            TokenError::DecimalsChanged => write!(f, "Decimals can't be changed"),
+        }?;
+        write!(f, " (E{})", self.code())
+    }
+}
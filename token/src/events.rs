@@ -0,0 +1,266 @@
+//! NEP-297 events, logged as `EVENT_JSON:{"standard":..,"version":..,"event":..,"data":[..]}`.
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::{env, AccountId};
+
+use crate::AllowanceExpiry;
+
+const NEP141_STANDARD: &str = "nep141";
+const NEP141_VERSION: &str = "1.0.0";
+
+const SCALE_TOKEN_STANDARD: &str = "scale_token";
+const SCALE_TOKEN_VERSION: &str = "1.0.0";
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a, T> {
+    standard: &'a str,
+    version: &'a str,
+    event: &'a str,
+    data: &'a [T],
+}
+
+pub trait Event: Serialize + Sized {
+    const STANDARD: &'static str;
+    const VERSION: &'static str;
+    const EVENT: &'static str;
+
+    fn emit(self) {
+        Self::emit_many(&[self])
+    }
+
+    fn emit_many(data: &[Self]) {
+        env::log(Self::to_log(data).as_bytes());
+    }
+
+    fn to_log(data: &[Self]) -> String {
+        let log = EventLog {
+            standard: Self::STANDARD,
+            version: Self::VERSION,
+            event: Self::EVENT,
+            data,
+        };
+        format!(
+            "EVENT_JSON:{}",
+            near_sdk::serde_json::to_string(&log).unwrap()
+        )
+    }
+}
+
+macro_rules! impl_event {
+    ($name:ty, $standard:expr, $version:expr, $event:expr) => {
+        impl Event for $name {
+            const STANDARD: &'static str = $standard;
+            const VERSION: &'static str = $version;
+            const EVENT: &'static str = $event;
+        }
+    };
+}
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtMint<'a> {
+    pub owner_id: &'a AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+impl_event!(FtMint<'_>, NEP141_STANDARD, NEP141_VERSION, "ft_mint");
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtBurn<'a> {
+    pub owner_id: &'a AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+impl_event!(FtBurn<'_>, NEP141_STANDARD, NEP141_VERSION, "ft_burn");
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FtTransfer<'a> {
+    pub old_owner_id: &'a AccountId,
+    pub new_owner_id: &'a AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<&'a str>,
+}
+impl_event!(FtTransfer<'_>, NEP141_STANDARD, NEP141_VERSION, "ft_transfer");
+
+/// The allowance of `escrow_account_id` was set to `amount`.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Approve<'a> {
+    pub owner_id: &'a AccountId,
+    pub escrow_account_id: &'a AccountId,
+    pub amount: U128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<&'a AllowanceExpiry>,
+}
+impl_event!(Approve<'_>, SCALE_TOKEN_STANDARD, SCALE_TOKEN_VERSION, "approve");
+
+/// `escrow_account_id` locked `amount` of the tokens of `owner_id`.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Lock<'a> {
+    pub owner_id: &'a AccountId,
+    pub escrow_account_id: &'a AccountId,
+    pub amount: U128,
+}
+impl_event!(Lock<'_>, SCALE_TOKEN_STANDARD, SCALE_TOKEN_VERSION, "lock");
+
+/// `escrow_account_id` unlocked or spent `amount` of the tokens it locked.
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Unlock<'a> {
+    pub owner_id: &'a AccountId,
+    pub escrow_account_id: &'a AccountId,
+    pub amount: U128,
+}
+impl_event!(Unlock<'_>, SCALE_TOKEN_STANDARD, SCALE_TOKEN_VERSION, "unlock");
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RevokeAllAllowances<'a> {
+    pub owner_id: &'a AccountId,
+    pub count: u32,
+}
+impl_event!(
+    RevokeAllAllowances<'_>,
+    SCALE_TOKEN_STANDARD,
+    SCALE_TOKEN_VERSION,
+    "revoke_all_allowances"
+);
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageDeposit<'a> {
+    pub account_id: &'a AccountId,
+    pub amount: U128,
+}
+impl_event!(
+    StorageDeposit<'_>,
+    SCALE_TOKEN_STANDARD,
+    SCALE_TOKEN_VERSION,
+    "storage_deposit"
+);
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageRefund<'a> {
+    pub account_id: &'a AccountId,
+    pub amount: U128,
+}
+impl_event!(
+    StorageRefund<'_>,
+    SCALE_TOKEN_STANDARD,
+    SCALE_TOKEN_VERSION,
+    "storage_refund"
+);
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PooledNearSync {
+    pub old_total_pooled_near: U128,
+    pub new_total_pooled_near: U128,
+    pub total_shares: U128,
+}
+impl_event!(
+    PooledNearSync,
+    SCALE_TOKEN_STANDARD,
+    SCALE_TOKEN_VERSION,
+    "pooled_near_sync"
+);
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct MetadataUpdate<'a> {
+    pub updated_by: &'a AccountId,
+}
+impl_event!(
+    MetadataUpdate<'_>,
+    SCALE_TOKEN_STANDARD,
+    SCALE_TOKEN_VERSION,
+    "metadata_update"
+);
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoleGranted<'a> {
+    pub role: &'a str,
+    pub account_id: &'a AccountId,
+    pub sender_id: &'a AccountId,
+}
+impl_event!(
+    RoleGranted<'_>,
+    SCALE_TOKEN_STANDARD,
+    SCALE_TOKEN_VERSION,
+    "role_granted"
+);
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RoleRevoked<'a> {
+    pub role: &'a str,
+    pub account_id: &'a AccountId,
+    pub sender_id: &'a AccountId,
+}
+impl_event!(
+    RoleRevoked<'_>,
+    SCALE_TOKEN_STANDARD,
+    SCALE_TOKEN_VERSION,
+    "role_revoked"
+);
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Pause<'a> {
+    pub account_id: &'a AccountId,
+}
+impl_event!(Pause<'_>, SCALE_TOKEN_STANDARD, SCALE_TOKEN_VERSION, "pause");
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Unpause<'a> {
+    pub account_id: &'a AccountId,
+}
+impl_event!(Unpause<'_>, SCALE_TOKEN_STANDARD, SCALE_TOKEN_VERSION, "unpause");
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenizerProposed<'a> {
+    pub old_tokenizer_id: &'a AccountId,
+    pub new_tokenizer_id: &'a AccountId,
+}
+impl_event!(
+    TokenizerProposed<'_>,
+    SCALE_TOKEN_STANDARD,
+    SCALE_TOKEN_VERSION,
+    "tokenizer_proposed"
+);
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenizerProposalCancelled<'a> {
+    pub new_tokenizer_id: &'a AccountId,
+}
+impl_event!(
+    TokenizerProposalCancelled<'_>,
+    SCALE_TOKEN_STANDARD,
+    SCALE_TOKEN_VERSION,
+    "tokenizer_proposal_cancelled"
+);
+
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenizerAccepted<'a> {
+    pub old_tokenizer_id: &'a AccountId,
+    pub new_tokenizer_id: &'a AccountId,
+}
+impl_event!(
+    TokenizerAccepted<'_>,
+    SCALE_TOKEN_STANDARD,
+    SCALE_TOKEN_VERSION,
+    "tokenizer_accepted"
+);
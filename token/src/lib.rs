@@ -1,48 +1,107 @@
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap};
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet, Vector};
 use near_sdk::json_types::{U128};
 use near_sdk::{
-    env, near_bindgen, AccountId, Balance, Promise, StorageUsage
+    env, near_bindgen, AccountId, Balance, StorageUsage
 };
 
-//@TODO: Scale factor
+use crate::events::{
+    Approve, Event, FtBurn, FtMint, FtTransfer, PooledNearSync, RevokeAllAllowances, Unlock,
+};
+use crate::util::{mul_div, mul_div_ceil};
+
+mod allowance_expiry;
+mod batch;
+mod checkpoint;
+mod errors;
+mod events;
+mod lock;
+mod nep141;
+mod nep145;
+mod nep148;
+mod pausable;
+mod roles;
+mod tokenizer;
+mod upgrade;
+pub mod util;
+
+pub use allowance_expiry::AllowanceExpiry;
+pub use checkpoint::{AccountCheckpoint, SupplyCheckpoint};
+pub use errors::TokenError;
+pub use nep141::ext_fungible_token_receiver;
+pub use nep145::{StorageAccount, StorageBalance, StorageBalanceBounds, StoragePayer};
+pub use nep148::{FungibleTokenMetadata, FT_METADATA_SPEC};
+pub use roles::Role;
+pub use upgrade::{AccountV1, ScaleTokenV1, VersionedScaleToken, STATE_VERSION};
 
 /// Price per 1 byte of storage from mainnet genesis config.
 const STORAGE_PRICE_PER_BYTE: Balance = 100_000_000_000_000_000_000;
 
-/// Contains balance and allowances information for one account.
+/// Prefixes of all persistent collections. A prefix starts with the variant index, so key
+/// spaces of different collections never overlap. New variants must be appended at the end.
+#[derive(BorshSerialize)]
+pub(crate) enum StorageKey {
+    Accounts,
+    Allowances { account_hash: Vec<u8> },
+    Holders,
+    AccountCheckpoints,
+    AccountCheckpointsEntry { account_hash: Vec<u8> },
+    SupplyCheckpoints,
+    Roles,
+    RoleMembers { role: Role },
+    StorageAccounts,
+    LegacyAccounts,
+    AccountPayers,
+    StorageCredits,
+    AllowanceExpiries,
+    LockedShares,
+    LockedTotals,
+}
+
+impl StorageKey {
+    pub(crate) fn into_prefix(self) -> Vec<u8> {
+        self.try_to_vec().unwrap()
+    }
+}
+
+/// Contains shares and allowances information for one account.
+/// The balance of the account is `shares * total_supply / total_shares`.
 #[derive(BorshDeserialize, BorshSerialize)]
 pub struct Account {
-    pub balance: Balance,
-    pub allowances: LookupMap<Vec<u8>, Balance>,
+    pub shares: Balance,
+    /// Escrow AccountID -> Allowance, enumerable by the owner.
+    pub allowances: UnorderedMap<AccountId, Balance>,
     pub allowances_count: u32,
 }
 
 impl Account {
     pub fn new(account_hash: Vec<u8>) -> Self {
         Self {
-            balance: 0,
-            allowances: LookupMap::new(account_hash),
+            shares: 0,
+            allowances: UnorderedMap::new(StorageKey::Allowances { account_hash }.into_prefix()),
             allowances_count: 0
         }
     }
 
     pub fn set_allowance(&mut self, escrow_account_id: &AccountId, allowance: Balance) {
-        let escrow_hash = env::sha256(escrow_account_id.as_bytes());
         if allowance > 0 {
-            if self.allowances.insert(&escrow_hash, &allowance).is_none() {
+            if self.allowances.insert(escrow_account_id, &allowance).is_none() {
                 self.allowances_count += 1;
             }
         } else {
-            if self.allowances.remove(&escrow_hash).is_some() {
+            if self.allowances.remove(escrow_account_id).is_some() {
                 self.allowances_count -= 1;
             }
         }
     }
 
     pub fn get_allowance(&self, escrow_account_id: &AccountId) -> Balance {
-        let escrow_hash = env::sha256(escrow_account_id.as_bytes());
-        self.allowances.get(&escrow_hash).unwrap_or(0)
+        self.allowances.get(escrow_account_id).unwrap_or(0)
+    }
+
+    pub fn clear_allowances(&mut self) {
+        self.allowances.clear();
+        self.allowances_count = 0;
     }
 }
 
@@ -52,39 +111,119 @@ pub struct ScaleToken {
     /// sha256(AccountID) -> Account details.
     pub accounts: LookupMap<Vec<u8>, Account>,
 
-    /// Total supply of the all token.
+    /// sha256(AccountID) -> Account details of v1, under the prefix b"a". An account is
+    /// moved to `accounts` on its next update, the record stays without the balance while
+    /// it has allowances under the v1 keys.
+    pub legacy_accounts: LookupMap<Vec<u8>, AccountV1>,
+
+    /// AccountIDs with positive shares.
+    pub holders: UnorderedSet<AccountId>,
+
+    /// Total supply of the all token, equal to the total pooled NEAR.
     pub total_supply: Balance,
 
-    /// 토큰이 제공하는 소수점자리 18u8
-    pub decimals: u8,
+    /// Total shares of the all accounts.
+    pub total_shares: Balance,
+
+    /// sha256(AccountID) -> Shares of the account per epoch.
+    pub account_checkpoints: LookupMap<Vec<u8>, Vector<AccountCheckpoint>>,
+
+    /// Total supply and total shares per epoch.
+    pub supply_checkpoints: Vector<SupplyCheckpoint>,
+
+    /// NEP-148 metadata, 토큰이 제공하는 소수점자리 포함
+    pub metadata: FungibleTokenMetadata,
 
     /// Tokenizer
     pub tokenizer: AccountId,
+
+    /// Tokenizer proposed by the admin, waiting to accept.
+    pub pending_tokenizer: Option<AccountId>,
+
+    /// Role -> AccountIDs holding the role.
+    pub roles: LookupMap<Role, UnorderedSet<AccountId>>,
+
+    /// Blocks transfers, approvals and minting when set.
+    pub paused: bool,
+
+    /// sha256(AccountID) -> Prepaid storage (NEP-145).
+    pub storage_accounts: LookupMap<Vec<u8>, StorageAccount>,
+
+    /// Storage used by one registered account.
+    pub account_storage_usage: StorageUsage,
+
+    /// sha256(AccountID) -> Payer of the storage of the account record, if it was created by
+    /// someone else than the account.
+    pub account_payers: LookupMap<Vec<u8>, StoragePayer>,
+
+    /// sha256(AccountID) -> Bytes of entries the account paid for that are not freed yet.
+    pub storage_credits: LookupMap<Vec<u8>, StorageUsage>,
+
+    /// sha256(owner AccountID) + sha256(escrow AccountID) -> Expiry of the allowance.
+    /// Allowances without an entry never expire.
+    pub allowance_expiries: LookupMap<Vec<u8>, AllowanceExpiry>,
+
+    /// sha256(owner AccountID) + sha256(escrow AccountID) -> Shares locked by the escrow.
+    pub locked_shares: LookupMap<Vec<u8>, Balance>,
+
+    /// sha256(AccountID) -> Shares of the account locked by all escrows.
+    pub locked_totals: LookupMap<Vec<u8>, Balance>,
+
+    /// Storage released to the payers of removed entries during the current call.
+    #[borsh_skip]
+    pub released_storage: StorageUsage,
 }
 
 impl Default for ScaleToken {
     fn default() -> Self {
-        panic!("ScaleToken should be initialized before usage")
+        TokenError::NotInitialized.panic()
     }
 }
 
 #[near_bindgen]
 impl ScaleToken {
-    /// Initializes the contract with the given total supply owned by the given `owner_id`.
+    /// Initializes the contract with the given Tokenizer, admin and metadata.
+    /// The Tokenizer gets the minter and burner roles, the admin gets the admin and
+    /// metadata-manager roles.
     #[init]
-    pub fn new(tokenizer_id: AccountId) -> Self {
-        assert!(!env::state_exists(), "Already initialized");
-        let st = Self {
-            accounts: LookupMap::new(b"a".to_vec()),
+    pub fn new(tokenizer_id: AccountId, admin_id: AccountId, metadata: FungibleTokenMetadata) -> Self {
+        if env::state_exists() {
+            TokenError::AlreadyInitialized.panic();
+        }
+        metadata.assert_valid();
+        let mut st = Self {
+            accounts: LookupMap::new(StorageKey::Accounts.into_prefix()),
+            legacy_accounts: LookupMap::new(StorageKey::LegacyAccounts.into_prefix()),
+            holders: UnorderedSet::new(StorageKey::Holders.into_prefix()),
             total_supply: 0u128,
-            decimals: 18u8,
-            tokenizer: tokenizer_id
+            total_shares: 0u128,
+            account_checkpoints: LookupMap::new(StorageKey::AccountCheckpoints.into_prefix()),
+            supply_checkpoints: Vector::new(StorageKey::SupplyCheckpoints.into_prefix()),
+            metadata,
+            tokenizer: tokenizer_id.clone(),
+            pending_tokenizer: None,
+            roles: LookupMap::new(StorageKey::Roles.into_prefix()),
+            paused: false,
+            storage_accounts: LookupMap::new(StorageKey::StorageAccounts.into_prefix()),
+            account_storage_usage: 0,
+            account_payers: LookupMap::new(StorageKey::AccountPayers.into_prefix()),
+            storage_credits: LookupMap::new(StorageKey::StorageCredits.into_prefix()),
+            allowance_expiries: LookupMap::new(StorageKey::AllowanceExpiries.into_prefix()),
+            locked_shares: LookupMap::new(StorageKey::LockedShares.into_prefix()),
+            locked_totals: LookupMap::new(StorageKey::LockedTotals.into_prefix()),
+            released_storage: 0,
         };
+        st.measure_account_storage_usage();
+        st.internal_grant_role(Role::Admin, &admin_id);
+        st.internal_grant_role(Role::MetadataManager, &admin_id);
+        st.internal_grant_role(Role::Minter, &tokenizer_id);
+        st.internal_grant_role(Role::Burner, &tokenizer_id);
+        upgrade::write_state_version();
         st
     }
 
     pub fn decimals(&self) -> u8 {
-        return self.decimals;
+        return self.metadata.decimals;
     }
 
     /// Returns total supply of tokens.
@@ -94,153 +233,602 @@ impl ScaleToken {
 
     /// Returns balance of the `owner_id` account.
     pub fn get_balance(&self, owner_id: AccountId) -> U128 {
-        self.get_account(&owner_id).balance.into()
+        self.internal_balance_of(&owner_id).into()
+    }
+
+    /// Returns up to `limit` holders starting at `from_index`. Holders of v1 are listed once
+    /// their account is moved, see `get_holder_count`.
+    pub fn get_holders(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+        let holders = self.holders.as_vector();
+        let end = std::cmp::min(from_index.saturating_add(limit), holders.len());
+        (from_index..end).map(|index| holders.get(index).unwrap()).collect()
+    }
+
+    /// Returns the number of accounts with a positive balance. A v1 account joins the holders
+    /// when it is moved, by its next update or by `migrate_accounts`. The count is accurate
+    /// once every v1 account with a balance is passed to `migrate_accounts`.
+    pub fn get_holder_count(&self) -> u64 {
+        self.holders.len()
+    }
+
+    /// Returns shares of the `owner_id` account.
+    pub fn get_shares(&self, owner_id: AccountId) -> U128 {
+        self.get_account(&owner_id).shares.into()
+    }
+
+    pub fn get_total_shares(&self) -> U128 {
+        self.total_shares.into()
+    }
+
+    /// Returns the amount of pooled NEAR represented by `shares`.
+    pub fn get_pooled_near_by_shares(&self, shares: U128) -> U128 {
+        self.shares_to_amount(shares.into()).into()
+    }
+
+    /// Returns the shares that `amount` of pooled NEAR is worth.
+    pub fn get_shares_by_pooled_near(&self, amount: U128) -> U128 {
+        self.amount_to_shares(amount.into()).into()
     }
 
+    /// Sets the allowance of `escrow_account_id`, valid until `expires_at` if given.
+    /// Storage is paid from the prepaid storage balance of the caller, topped up with the
+    /// attached deposit.
+    /// Prefer `inc_allowance`/`dec_allowance`, overwriting can be front-run by the escrow.
     #[payable]
-    pub fn approve(&mut self, escrow_account_id: AccountId, amount: U128) {
-        let initial_storage = env::storage_usage();
-        assert!(
-            env::is_valid_account_id(escrow_account_id.as_bytes()),
-            "Escrow account ID is invalid"
-        );
-        let owner_id = env::predecessor_account_id();
-        if escrow_account_id == owner_id {
-            env::panic(b"Can not increment allowance for yourself");
+    pub fn approve(
+        &mut self,
+        escrow_account_id: AccountId,
+        amount: U128,
+        expires_at: Option<AllowanceExpiry>,
+    ) {
+        self.assert_not_paused();
+        if expires_at.as_ref().map_or(false, |expiry| expiry.is_expired()) {
+            TokenError::AllowanceExpiryInPast.panic();
         }
-        let mut account = self.get_account(&owner_id);
-        account.set_allowance(&escrow_account_id, amount.into());
-        self.set_account(&owner_id, &account);
-        self.refund_storage(initial_storage);
+        self.internal_update_allowance(
+            &escrow_account_id,
+            TokenError::SelfAllowanceIncrement,
+            expires_at,
+            |_| amount.into(),
+        );
     }
 
+    /// Increments the allowance of `escrow_account_id` by `amount`, saturating at `u128::MAX`.
+    /// The expiry of the allowance is kept. An expired allowance is removed first, the
+    /// increment starts a new allowance without expiry.
     #[payable]
-    pub fn transfer_from(&mut self, owner_id: AccountId, new_owner_id: AccountId, amount: U128) {
-        let initial_storage = env::storage_usage();
-        assert!(
-            env::is_valid_account_id(new_owner_id.as_bytes()),
-            "New owner's account ID is invalid"
-        );
-        let amount = amount.into();
-        if amount == 0 {
-            env::panic(b"Can't transfer 0 tokens");
-        }
-        assert_ne!(
-            owner_id, new_owner_id,
-            "The new owner should be different from the current owner"
+    pub fn inc_allowance(&mut self, escrow_account_id: AccountId, amount: U128) {
+        self.assert_not_paused();
+        let expires_at =
+            self.internal_allowance_expiry(&env::predecessor_account_id(), &escrow_account_id);
+        self.internal_update_allowance(
+            &escrow_account_id,
+            TokenError::SelfAllowanceIncrement,
+            expires_at,
+            |allowance| allowance.saturating_add(amount.into()),
         );
-        // Retrieving the account from the state.
-        let mut account = self.get_account(&owner_id);
+    }
 
-        env::log(format!("transfer_from {} to {}, {}", owner_id, new_owner_id, amount).as_bytes());
+    /// Decrements the allowance of `escrow_account_id` by `amount`, saturating at 0.
+    /// An expired allowance is removed. The storage of a removed allowance is credited back.
+    #[payable]
+    pub fn dec_allowance(&mut self, escrow_account_id: AccountId, amount: U128) {
+        let expires_at =
+            self.internal_allowance_expiry(&env::predecessor_account_id(), &escrow_account_id);
+        self.internal_update_allowance(
+            &escrow_account_id,
+            TokenError::SelfAllowanceDecrement,
+            expires_at,
+            |allowance| allowance.saturating_sub(amount.into()),
+        );
+    }
 
-        // Checking and updating unlocked balance
-        if account.balance < amount {
-            env::panic(b"Not enough balance");
-        }
-        account.balance -= amount;
+    /// Storage of a new receiver is paid from the prepaid storage balance of the caller.
+    /// An escrow spends the tokens it locked first, then its allowance. An expired allowance
+    /// is treated as zero, the next `approve`, `inc_allowance` or `dec_allowance` of the owner
+    /// or `remove_expired_allowances` frees it.
+    #[payable]
+    pub fn transfer_from(&mut self, owner_id: AccountId, new_owner_id: AccountId, amount: U128) {
+        self.assert_not_paused();
+        self.deposit_attached_storage();
+        let initial_storage = env::storage_usage();
+        let amount = amount.into();
 
-        // If transferring by escrow, need to check and update allowance.
+        // If transferring by escrow, need to release its locks and update allowance.
         let escrow_account_id = env::predecessor_account_id();
         if escrow_account_id != owner_id {
-            let allowance = account.get_allowance(&escrow_account_id);
-            if allowance < amount {
-                env::panic(b"Not enough allowance");
+            let locked = self.internal_locked_shares(&owner_id, &escrow_account_id);
+            let locked_amount = std::cmp::min(amount, self.shares_to_amount(locked));
+            if locked_amount > 0 {
+                let shares =
+                    self.locked_shares_of_amount(&owner_id, &escrow_account_id, locked_amount);
+                self.decrease_locked_shares(&owner_id, &escrow_account_id, shares);
+                Unlock {
+                    owner_id: &owner_id,
+                    escrow_account_id: &escrow_account_id,
+                    amount: locked_amount.into(),
+                }
+                .emit();
+            }
+            if amount > locked_amount {
+                self.spend_allowance(&owner_id, &escrow_account_id, amount - locked_amount);
             }
-            account.set_allowance(&escrow_account_id, allowance - amount);
         }
 
-        // Saving the account back to the state.
-        self.set_account(&owner_id, &account);
-
-        // Deposit amount to the new owner and save the new account to the state.
-        let mut new_account = self.get_account(&new_owner_id);
-        new_account.balance += amount;
-        self.set_account(&new_owner_id, &new_account);
-        self.refund_storage(initial_storage);
+        self.internal_transfer(&owner_id, &new_owner_id, amount, None);
+        self.settle_storage(&escrow_account_id, initial_storage);
     }
 
     #[payable]
     pub fn transfer(&mut self, new_owner_id: AccountId, amount: U128) {
         // NOTE: New owner's Account ID checked in transfer_from.
-        // Storage fees are also refunded in transfer_from.
+        // Storage is also settled in transfer_from.
         self.transfer_from(env::predecessor_account_id(), new_owner_id, amount);
     }
 
+    /// Returns the allowance of `escrow_account_id`, zero once it expired.
+    /// See `get_allowance_expiry` for the expiry.
     pub fn get_allowance(&self, owner_id: AccountId, escrow_account_id: AccountId) -> U128 {
-        assert!(
-            env::is_valid_account_id(escrow_account_id.as_bytes()),
-            "Escrow account ID is invalid"
-        );
-        self.get_account(&owner_id)
-            .get_allowance(&escrow_account_id)
+        if !env::is_valid_account_id(escrow_account_id.as_bytes()) {
+            TokenError::InvalidEscrowAccountId.panic();
+        }
+        let account = self.get_account(&owner_id);
+        self.allowance_of(&owner_id, &account, &escrow_account_id)
             .into()
     }
 
-    /// Mints given amount to the smart contract caller
-    #[allow(dead_code)]
-    fn mint_to(&mut self, amount: u128, target: AccountId) -> U128 {
-        let caller = env::predecessor_account_id();
-        if caller != self.tokenizer {
-            env::panic(b"Caller is not Tokenizer");
+    /// Returns up to `limit` allowances granted by `owner_id`, starting at `from_index`.
+    /// Expired allowances are listed as zero until they are removed. Allowances granted in
+    /// v1 are listed once they are moved, see `get_allowance`.
+    pub fn get_allowances(
+        &self,
+        owner_id: AccountId,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<(AccountId, U128)> {
+        let account = self.get_account(&owner_id);
+        let escrow_account_ids = account.allowances.keys_as_vector();
+        let allowances = account.allowances.values_as_vector();
+        let end = std::cmp::min(from_index.saturating_add(limit), escrow_account_ids.len());
+        (from_index..end)
+            .map(|index| {
+                let escrow_account_id = escrow_account_ids.get(index).unwrap();
+                let allowance = if self.is_allowance_expired(&owner_id, &escrow_account_id) {
+                    0
+                } else {
+                    allowances.get(index).unwrap()
+                };
+                (escrow_account_id, allowance.into())
+            })
+            .collect()
+    }
+
+    /// Removes every allowance granted by the caller and credits the freed storage
+    /// to the storage balance of the caller. Returns the number of revoked allowances.
+    pub fn revoke_all_allowances(&mut self) -> u32 {
+        let initial_storage = env::storage_usage();
+        let owner_id = env::predecessor_account_id();
+        // Allowances of v1 are keyed by hash and can't be listed.
+        let legacy_allowances = self.legacy_allowances_count(&owner_id);
+        if legacy_allowances > 0 {
+            TokenError::LegacyAllowancesLeft(legacy_allowances).panic();
+        }
+        let mut account = self.get_account(&owner_id);
+        let revoked = account.allowances_count;
+        let allowance_storage = env::storage_usage();
+        for escrow_account_id in account.allowances.keys() {
+            self.set_allowance_expiry(&owner_id, &escrow_account_id, None);
+        }
+        account.clear_allowances();
+        self.track_allowance_storage(&owner_id, allowance_storage);
+        self.set_account(&owner_id, &mut account);
+        self.settle_storage(&owner_id, initial_storage);
+        RevokeAllAllowances {
+            owner_id: &owner_id,
+            count: revoked,
+        }
+        .emit();
+        revoked
+    }
+
+    /// Mints `amount` tokens to `account_id`. Only a minter can mint.
+    /// Storage for a new account is paid from the prepaid storage balance of the minter.
+    #[payable]
+    pub fn mint(&mut self, account_id: AccountId, amount: U128) -> U128 {
+        self.assert_role(Role::Minter);
+        self.assert_not_paused();
+        self.deposit_attached_storage();
+        let initial_storage = env::storage_usage();
+        let amount = amount.into();
+        if amount == 0 {
+            TokenError::ZeroMint.panic();
+        }
+        let shares = self.amount_to_shares(amount);
+        if shares == 0 {
+            TokenError::MintTooSmall.panic();
+        }
+        self.total_supply = self
+            .total_supply
+            .checked_add(amount)
+            .unwrap_or_else(|| TokenError::TotalSupplyOverflow.panic());
+        self.total_shares = self
+            .total_shares
+            .checked_add(shares)
+            .unwrap_or_else(|| TokenError::TotalSharesOverflow.panic());
+
+        let mut account = self.get_account(&account_id);
+        account.shares = account
+            .shares
+            .checked_add(shares)
+            .unwrap_or_else(|| TokenError::BalanceOverflow.panic());
+        self.set_account(&account_id, &mut account);
+        self.record_supply_checkpoint();
+        self.settle_storage(&env::predecessor_account_id(), initial_storage);
+        FtMint {
+            owner_id: &account_id,
+            amount: amount.into(),
+            memo: None,
+        }
+        .emit();
+        self.shares_to_amount(account.shares).into()
+    }
+
+    /// Burns `amount` tokens from `account_id`. Only a burner can burn.
+    /// Freed storage is credited to the storage balance of the burner.
+    pub fn burn_from(&mut self, account_id: AccountId, amount: U128) -> U128 {
+        let initial_storage = env::storage_usage();
+        self.assert_role(Role::Burner);
+        let amount = amount.into();
+        if amount == 0 {
+            TokenError::ZeroBurn.panic();
+        }
+        let mut account = self.get_account(&account_id);
+        if amount > self.shares_to_amount(account.shares) {
+            TokenError::NotEnoughBalance.panic();
         }
+        // Rounding up, so burning never leaves the pool with less NEAR per share.
+        let shares = mul_div_ceil(amount, self.total_shares, self.total_supply)
+            .unwrap_or_else(|| TokenError::ConversionOverflow.panic());
+        self.assert_unlocked_shares(&account_id, &account, shares);
+        if amount > self.total_supply {
+            TokenError::TotalSupplyUnderflow.panic();
+        }
+        account.shares -= shares;
+        self.total_supply -= amount;
+        self.total_shares -= shares;
+        self.set_account(&account_id, &mut account);
+        self.record_supply_checkpoint();
+        self.settle_storage(&env::predecessor_account_id(), initial_storage);
+        FtBurn {
+            owner_id: &account_id,
+            amount: amount.into(),
+            memo: None,
+        }
+        .emit();
+        self.shares_to_amount(account.shares).into()
+    }
 
-        self.total_supply += amount;
-        let mut account = self.get_account(&target);
-        account.balance += amount;
-        self.set_account(&target, &account);
-        account.balance.into()
+    /// Updates the total pooled NEAR after the Tokenizer synced staking rewards.
+    /// Balances of all accounts change in proportion to their shares. Only a minter can sync.
+    /// The pooled NEAR can be zero only while there are no shares.
+    pub fn sync_pooled_near(&mut self, total_pooled_near: U128) {
+        self.assert_role(Role::Minter);
+        let initial_storage = env::storage_usage();
+        let total_pooled_near: Balance = total_pooled_near.into();
+        if total_pooled_near == 0 && self.total_shares > 0 {
+            TokenError::ZeroPooledNear.panic();
+        }
+        PooledNearSync {
+            old_total_pooled_near: self.total_supply.into(),
+            new_total_pooled_near: total_pooled_near.into(),
+            total_shares: self.total_shares.into(),
+        }
+        .emit();
+        self.total_supply = total_pooled_near;
+        self.record_supply_checkpoint();
+        self.settle_storage(&env::predecessor_account_id(), initial_storage);
     }
 }
 
 impl ScaleToken {
-    fn get_account(&self, owner_id: &AccountId) -> Account {
-        assert!(
-            env::is_valid_account_id(owner_id.as_bytes()),
-            "Owner's account ID is invalid"
-        );
+    fn internal_update_allowance<F: FnOnce(Balance) -> Balance>(
+        &mut self,
+        escrow_account_id: &AccountId,
+        self_allowance_error: TokenError,
+        expires_at: Option<AllowanceExpiry>,
+        update: F,
+    ) {
+        self.deposit_attached_storage();
+        let initial_storage = env::storage_usage();
+        if !env::is_valid_account_id(escrow_account_id.as_bytes()) {
+            TokenError::InvalidEscrowAccountId.panic();
+        }
+        let owner_id = env::predecessor_account_id();
+        if *escrow_account_id == owner_id {
+            self_allowance_error.panic();
+        }
+        let mut account = self.get_account(&owner_id);
+        // An expired allowance counts as zero and is overwritten together with its expiry,
+        // its storage is reused or credited back.
+        let allowance = update(self.allowance_of(&owner_id, &account, escrow_account_id));
+        // A removed allowance takes its expiry along.
+        let expires_at = expires_at.filter(|expiry| allowance > 0 && !expiry.is_expired());
+        let allowance_storage = env::storage_usage();
+        account.set_allowance(escrow_account_id, allowance);
+        self.take_legacy_allowance(&owner_id, escrow_account_id);
+        self.set_allowance_expiry(&owner_id, escrow_account_id, expires_at.clone());
+        self.track_allowance_storage(&owner_id, allowance_storage);
+        self.set_account(&owner_id, &mut account);
+        self.settle_storage(&owner_id, initial_storage);
+        Approve {
+            owner_id: &owner_id,
+            escrow_account_id,
+            amount: allowance.into(),
+            expires_at: expires_at.as_ref(),
+        }
+        .emit();
+    }
+
+    /// Takes `amount` from the allowance of `escrow_account_id`.
+    pub(crate) fn spend_allowance(
+        &mut self,
+        owner_id: &AccountId,
+        escrow_account_id: &AccountId,
+        amount: Balance,
+    ) {
+        let mut account = self.get_account(owner_id);
+        let allowance = self.allowance_of(owner_id, &account, escrow_account_id);
+        if allowance < amount {
+            TokenError::NotEnoughAllowance.panic();
+        }
+        let allowance_storage = env::storage_usage();
+        account.set_allowance(escrow_account_id, allowance - amount);
+        self.take_legacy_allowance(owner_id, escrow_account_id);
+        let expires_at = self.internal_allowance_expiry(owner_id, escrow_account_id);
+        // The expiry stays while the escrow holds locks, `unlock` restores the allowance.
+        if allowance == amount && self.internal_locked_shares(owner_id, escrow_account_id) == 0 {
+            self.set_allowance_expiry(owner_id, escrow_account_id, None);
+        }
+        self.track_allowance_storage(owner_id, allowance_storage);
+        self.set_account(owner_id, &mut account);
+        Approve {
+            owner_id,
+            escrow_account_id,
+            amount: (allowance - amount).into(),
+            expires_at: expires_at.as_ref().filter(|_| allowance > amount),
+        }
+        .emit();
+    }
+
+    /// Returns the allowance of `escrow_account_id` in `account`, zero once it expired.
+    pub(crate) fn allowance_of(
+        &self,
+        owner_id: &AccountId,
+        account: &Account,
+        escrow_account_id: &AccountId,
+    ) -> Balance {
+        if self.is_allowance_expired(owner_id, escrow_account_id) {
+            return 0;
+        }
+        account
+            .allowances
+            .get(escrow_account_id)
+            .or_else(|| self.legacy_allowance(owner_id, escrow_account_id))
+            .unwrap_or(0)
+    }
+
+    /// Returns the allowance of `escrow_account_id` granted in v1 and not moved yet.
+    pub(crate) fn legacy_allowance(
+        &self,
+        owner_id: &AccountId,
+        escrow_account_id: &AccountId,
+    ) -> Option<Balance> {
+        let account_hash = env::sha256(owner_id.as_bytes());
+        self.legacy_accounts
+            .get(&account_hash)?
+            .allowances
+            .get(&env::sha256(escrow_account_id.as_bytes()))
+    }
+
+    /// Returns the number of allowances granted in v1 and not moved yet.
+    pub(crate) fn legacy_allowances_count(&self, owner_id: &AccountId) -> u32 {
+        let account_hash = env::sha256(owner_id.as_bytes());
+        self.legacy_accounts
+            .get(&account_hash)
+            .map_or(0, |legacy_account| legacy_account.allowances_count)
+    }
+
+    /// Removes the v1 allowance of `escrow_account_id` once the allowance is written under
+    /// the current keys. The v1 record goes with its last allowance if its balance moved.
+    pub(crate) fn take_legacy_allowance(
+        &mut self,
+        owner_id: &AccountId,
+        escrow_account_id: &AccountId,
+    ) -> Option<Balance> {
+        let account_hash = env::sha256(owner_id.as_bytes());
+        let mut legacy_account = self.legacy_accounts.get(&account_hash)?;
+        let allowance = legacy_account
+            .allowances
+            .remove(&env::sha256(escrow_account_id.as_bytes()))?;
+        legacy_account.allowances_count -= 1;
+        if legacy_account.allowances_count == 0 && legacy_account.balance == 0 {
+            self.legacy_accounts.remove(&account_hash);
+        } else {
+            self.legacy_accounts.insert(&account_hash, &legacy_account);
+        }
+        Some(allowance)
+    }
+
+    /// Moves `amount` tokens between two accounts without touching allowances or storage.
+    pub(crate) fn internal_transfer(
+        &mut self,
+        owner_id: &AccountId,
+        new_owner_id: &AccountId,
+        amount: Balance,
+        memo: Option<&str>,
+    ) {
+        if !env::is_valid_account_id(new_owner_id.as_bytes()) {
+            TokenError::InvalidNewOwnerAccountId.panic();
+        }
+        if amount == 0 {
+            TokenError::ZeroTransfer.panic();
+        }
+        if owner_id == new_owner_id {
+            TokenError::SelfTransfer.panic();
+        }
+        let shares = self.amount_to_shares(amount);
+        if shares == 0 {
+            TokenError::TransferTooSmall.panic();
+        }
+        // Retrieving the account from the state.
+        let mut account = self.get_account(owner_id);
+
+        // Checking and updating unlocked balance
+        self.assert_unlocked_shares(owner_id, &account, shares);
+        account.shares -= shares;
+
+        // Saving the account back to the state.
+        self.set_account(owner_id, &mut account);
+
+        // Deposit amount to the new owner and save the new account to the state.
+        let mut new_account = self.get_account(new_owner_id);
+        new_account.shares = new_account
+            .shares
+            .checked_add(shares)
+            .unwrap_or_else(|| TokenError::BalanceOverflow.panic());
+        self.set_account(new_owner_id, &mut new_account);
+        FtTransfer {
+            old_owner_id: owner_id,
+            new_owner_id,
+            amount: amount.into(),
+            memo,
+        }
+        .emit();
+    }
+
+    /// Moves `shares` from `owner_id` back to `new_owner_id` without the checks of a transfer,
+    /// the caller makes sure they are unlocked. Returns the refunded amount.
+    pub(crate) fn internal_refund(
+        &mut self,
+        owner_id: &AccountId,
+        new_owner_id: &AccountId,
+        shares: Balance,
+    ) -> Balance {
+        let amount = self.shares_to_amount(shares);
+        let mut account = self.get_account(owner_id);
+        account.shares -= shares;
+        self.set_account(owner_id, &mut account);
+
+        let mut new_account = self.get_account(new_owner_id);
+        new_account.shares += shares;
+        self.set_account(new_owner_id, &mut new_account);
+        FtTransfer {
+            old_owner_id: owner_id,
+            new_owner_id,
+            amount: amount.into(),
+            memo: Some("refund"),
+        }
+        .emit();
+        amount
+    }
+
+    pub(crate) fn internal_balance_of(&self, owner_id: &AccountId) -> Balance {
+        self.shares_to_amount(self.get_account(owner_id).shares)
+    }
+
+    pub(crate) fn shares_to_amount(&self, shares: Balance) -> Balance {
+        if self.total_shares == 0 {
+            return 0;
+        }
+        mul_div(shares, self.total_supply, self.total_shares)
+            .unwrap_or_else(|| TokenError::ConversionOverflow.panic())
+    }
+
+    /// Shares are minted 1:1 only into an empty pool. Pooled NEAR left without shares belongs
+    /// to nobody, so minting waits until a minter syncs it to zero.
+    pub(crate) fn amount_to_shares(&self, amount: Balance) -> Balance {
+        if self.total_shares == 0 && self.total_supply == 0 {
+            return amount;
+        }
+        if self.total_shares == 0 {
+            TokenError::PooledNearWithoutShares.panic();
+        }
+        if self.total_supply == 0 {
+            TokenError::ZeroPooledNear.panic();
+        }
+        mul_div(amount, self.total_shares, self.total_supply)
+            .unwrap_or_else(|| TokenError::ConversionOverflow.panic())
+    }
+
+    pub(crate) fn get_account(&self, owner_id: &AccountId) -> Account {
+        if !env::is_valid_account_id(owner_id.as_bytes()) {
+            TokenError::InvalidOwnerAccountId.panic();
+        }
         let account_hash = env::sha256(owner_id.as_bytes());
         self.accounts
             .get(&account_hash)
+            .or_else(|| {
+                self.legacy_accounts
+                    .get(&account_hash)
+                    .map(|legacy_account| legacy_account.to_account(account_hash.clone()))
+            })
             .unwrap_or_else(|| Account::new(account_hash))
     }
 
-    fn set_account(&mut self, owner_id: &AccountId, account: &Account) {
+    pub(crate) fn set_account(&mut self, owner_id: &AccountId, account: &mut Account) {
         let account_hash = env::sha256(owner_id.as_bytes());
-        if account.balance > 0 || account.allowances_count > 0 {
+        // The balance of a v1 record moves with this update, its allowances can't be listed
+        // and keep the record until they are moved.
+        let mut legacy = false;
+        if let Some(mut legacy_account) = self.legacy_accounts.get(&account_hash) {
+            legacy = legacy_account.balance > 0;
+            if legacy_account.allowances_count == 0 {
+                self.legacy_accounts.remove(&account_hash);
+            } else if legacy {
+                legacy_account.balance = 0;
+                self.legacy_accounts.insert(&account_hash, &legacy_account);
+            }
+        }
+        self.record_account_checkpoint(account_hash.clone(), account.shares);
+        // The holder entry comes and goes with the balance, it is settled by the caller.
+        if account.shares > 0 {
+            self.holders.insert(owner_id);
+        } else {
+            self.holders.remove(owner_id);
+        }
+        let initial_storage = env::storage_usage();
+        if account.shares > 0
+            || account.allowances_count > 0
+            || self.storage_accounts.contains_key(&account_hash)
+        {
             self.accounts.insert(&account_hash, &account);
         } else {
             self.accounts.remove(&account_hash);
         }
+        // Moving a legacy record is not a new entry.
+        if !legacy {
+            self.track_account_storage(owner_id, initial_storage);
+        }
     }
+}
 
-    fn refund_storage(&self, initial_storage: StorageUsage) {
-        let current_storage = env::storage_usage();
-        let attached_deposit = env::attached_deposit();
-        let refund_amount = if current_storage > initial_storage {
-            let required_deposit =
-                Balance::from(current_storage - initial_storage) * STORAGE_PRICE_PER_BYTE;
-            assert!(
-                required_deposit <= attached_deposit,
-                "The required attached deposit is {}, but the given attached deposit is is {}",
-                required_deposit,
-                attached_deposit,
-            );
-            attached_deposit - required_deposit
-        } else {
-            attached_deposit
-                + Balance::from(initial_storage - current_storage) * STORAGE_PRICE_PER_BYTE
-        };
-        if refund_amount > 0 {
-            env::log(format!("Refunding {} tokens for storage", refund_amount).as_bytes());
-            Promise::new(env::predecessor_account_id()).transfer(refund_amount);
-        }
+/// sha256(owner AccountID) + sha256(escrow AccountID), the key of per-escrow entries.
+pub(crate) fn escrow_key(owner_id: &AccountId, escrow_account_id: &AccountId) -> Vec<u8> {
+    let mut key = env::sha256(owner_id.as_bytes());
+    key.extend(env::sha256(escrow_account_id.as_bytes()));
+    key
+}
+
+pub(crate) fn assert_one_yocto() {
+    if env::attached_deposit() != 1 {
+        TokenError::RequiresOneYocto.panic();
     }
 }
 
 #[cfg(not(target_arch = "wasm32"))]
 #[cfg(test)]
 mod tests {
+    use near_sdk::json_types::Base64VecU8;
     use near_sdk::MockedBlockchain;
     use near_sdk::{testing_env, VMContext};
 
@@ -249,6 +837,9 @@ mod tests {
     fn tokenizer() -> AccountId {
         "tokenizer.near".to_string()
     }
+    fn admin() -> AccountId {
+        "admin.near".to_string()
+    }
     fn alice() -> AccountId {
         "alice.near".to_string()
     }
@@ -259,6 +850,18 @@ mod tests {
         "carol.near".to_string()
     }
 
+    fn metadata() -> FungibleTokenMetadata {
+        FungibleTokenMetadata {
+            spec: FT_METADATA_SPEC.to_string(),
+            name: "Scale Token".to_string(),
+            symbol: "SCALE".to_string(),
+            icon: None,
+            reference: None,
+            reference_hash: None,
+            decimals: 18,
+        }
+    }
+
     fn get_context(predecessor_account_id: AccountId) -> VMContext {
         VMContext {
             current_account_id: alice(),
@@ -285,7 +888,7 @@ mod tests {
         let context = get_context(carol());
         testing_env!(context);
         let total_supply = 0u128;
-        let contract = ScaleToken::new(tokenizer());
+        let contract = ScaleToken::new(tokenizer(), admin(), metadata());
         assert_eq!(contract.get_total_supply().0, total_supply);
         assert_eq!(contract.get_balance(tokenizer()).0, total_supply);
     }
@@ -296,18 +899,19 @@ mod tests {
     //     let context = get_context(carol());
     //     testing_env!(context);
     //     {
-    //         let _contract = ScaleToken::new(tokenizer());
+    //         let _contract = ScaleToken::new(tokenizer(), admin(), metadata());
     //     }
-    //     ScaleToken::new(tokenizer());
+    //     ScaleToken::new(tokenizer(), admin(), metadata());
     // }
 
     #[test]
     fn test_transfer_to_a_different_account_works() {
         let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
         testing_env!(context.clone());
         let mint_balance = 1_000_000_000_000_000u128;
-        let mut contract = ScaleToken::new(tokenizer());
-        contract.mint_to(mint_balance, carol());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
 
         context.predecessor_account_id = carol();
         context.storage_usage = env::storage_usage();
@@ -332,10 +936,11 @@ mod tests {
     #[should_panic(expected = "The new owner should be different from the current owner")]
     fn test_transfer_to_self_fails() {
         let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
         testing_env!(context.clone());
         let mint_balance = 1_000_000_000_000_000u128;
-        let mut contract = ScaleToken::new(tokenizer());
-        contract.mint_to(mint_balance, carol());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
 
         context.predecessor_account_id = carol();
         context.storage_usage = env::storage_usage();
@@ -349,89 +954,103 @@ mod tests {
     #[should_panic(expected = "Can not increment allowance for yourself")]
     fn test_increment_allowance_to_self_fails() {
         let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
         testing_env!(context.clone());
         let mint_balance = 1_000_000_000_000_000u128;
-        let mut contract = ScaleToken::new(tokenizer());
-        contract.mint_to(mint_balance, carol());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
 
         context.predecessor_account_id = carol();
         context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
         testing_env!(context.clone());
-        contract.approve(carol(), (mint_balance / 2).into());
+        contract.approve(carol(), (mint_balance / 2).into(), None);
     }
 
-    // #[test]
-    // #[should_panic(expected = "Can not decrement allowance for yourself")]
-    // fn test_decrement_allowance_to_self_fails() {
-    //     let mut context = get_context(tokenizer());
-    //     testing_env!(context.clone());
-    //     let mint_balance = 1_000_000_000_000_000u128;
-    //     let mut contract = ScaleToken::new(tokenizer());
-    //     contract.mint_to(mint_balance, carol());
-
-    //     context.predecessor_account_id = carol();
-    //     context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
-    //     testing_env!(context.clone());
-    //     contract.approve(carol(), (mint_balance / 2).into());
-    // }
+    #[test]
+    #[should_panic(expected = "Can not decrement allowance for yourself")]
+    fn test_decrement_allowance_to_self_fails() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mint_balance = 1_000_000_000_000_000u128;
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
 
-    // #[test]
-    // fn test_decrement_allowance_after_allowance_was_saturated() {
-    //     let mut context = get_context(tokenizer());
-    //     testing_env!(context.clone());
-    //     let mint_balance = 1_000_000_000_000_000u128;
-    //     let mut contract = ScaleToken::new(tokenizer());
-    //     contract.mint_to(mint_balance, carol());
-
-    //     context.predecessor_account_id = carol();
-    //     context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
-    //     testing_env!(context.clone());
-    //     contract.approve(bob(), (mint_balance / 2).into());
-    //     assert_eq!(contract.get_allowance(carol(), bob()), 0.into())
-    // }
+        context.predecessor_account_id = carol();
+        context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
+        testing_env!(context.clone());
+        contract.dec_allowance(carol(), (mint_balance / 2).into());
+    }
 
-    // #[test]
-    // fn test_increment_allowance_does_not_overflow() {
-    //     let mut context = get_context(tokenizer());
-    //     testing_env!(context.clone());
-    //     let mint_balance = 1_000_000_000_000_000u128;
-    //     let mut contract = ScaleToken::new(tokenizer());
-    //     contract.mint_to(mint_balance, carol());
-
-    //     context.predecessor_account_id = carol();
-    //     context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
-    //     testing_env!(context.clone());
-    //     contract.approve(bob(), (mint_balance * 2).into());
-    //     assert_eq!(
-    //         contract.get_allowance(carol(), bob()),
-    //         std::u128::MAX.into()
-    //     )
-    // }
+    #[test]
+    fn test_decrement_allowance_after_allowance_was_saturated() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mint_balance = 1_000_000_000_000_000u128;
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
+
+        context.predecessor_account_id = carol();
+        context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
+        testing_env!(context.clone());
+        contract.inc_allowance(bob(), (mint_balance / 2).into());
+        let registered = contract.storage_balance_of(carol()).unwrap();
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.dec_allowance(bob(), mint_balance.into());
+        assert_eq!(contract.get_allowance(carol(), bob()), 0.into());
+        assert_eq!(contract.get_account(&carol()).allowances_count, 0);
+        assert!(contract.storage_balance_of(carol()).unwrap().available.0 > registered.available.0);
+    }
+
+    #[test]
+    fn test_increment_allowance_does_not_overflow() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mint_balance = 1_000_000_000_000_000u128;
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
+
+        context.predecessor_account_id = carol();
+        context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
+        testing_env!(context.clone());
+        contract.inc_allowance(bob(), std::u128::MAX.into());
+        contract.inc_allowance(bob(), (mint_balance * 2).into());
+        assert_eq!(
+            contract.get_allowance(carol(), bob()),
+            std::u128::MAX.into()
+        );
+        assert_eq!(contract.get_account(&carol()).allowances_count, 1);
+    }
 
     #[test]
-    #[should_panic(
-        expected = "The required attached deposit is 12400000000000000000000, but the given attached deposit is is 0"
-    )]
-    fn test_increment_allowance_with_insufficient_attached_deposit() {
+    #[should_panic(expected = "The account carol.near is not registered")]
+    fn test_increment_allowance_without_storage_balance() {
         let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
         testing_env!(context.clone());
         let mint_balance = 1_000_000_000_000_000u128;
-        let mut contract = ScaleToken::new(tokenizer());
-        contract.mint_to(mint_balance, carol());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
 
         context.predecessor_account_id = carol();
         context.attached_deposit = 0;
         testing_env!(context.clone());
-        contract.approve(bob(), (mint_balance / 2).into());
+        contract.approve(bob(), (mint_balance / 2).into(), None);
     }
 
     #[test]
     fn test_carol_escrows_to_bob_transfers_to_alice() {
         let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
         testing_env!(context.clone());
         let mint_balance = 1_000_000_000_000_000u128;
-        let mut contract = ScaleToken::new(tokenizer());
-        contract.mint_to(mint_balance, carol());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
 
         context.predecessor_account_id = carol();
         context.storage_usage = env::storage_usage();
@@ -445,7 +1064,7 @@ mod tests {
         context.is_view = false;
         context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
         testing_env!(context.clone());
-        contract.approve(bob(), allowance.into());
+        contract.approve(bob(), allowance.into(), None);
         context.storage_usage = env::storage_usage();
         context.account_balance = env::account_balance();
 
@@ -456,7 +1075,7 @@ mod tests {
 
         // Acting as bob now
         context.is_view = false;
-        context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
+        context.attached_deposit = STORAGE_PRICE_PER_BYTE * 2000;
         context.predecessor_account_id = bob();
         testing_env!(context.clone());
         contract.transfer_from(carol(), alice(), transfer_amount.into());
@@ -480,10 +1099,11 @@ mod tests {
     #[test]
     fn test_carol_escrows_to_bob_locks_and_transfers_to_alice() {
         let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
         testing_env!(context.clone());
         let mint_balance = 1_000_000_000_000_000u128;
-        let mut contract = ScaleToken::new(tokenizer());
-        contract.mint_to(mint_balance, carol());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
 
         context.predecessor_account_id = carol();
 
@@ -498,7 +1118,7 @@ mod tests {
         context.is_view = false;
         context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
         testing_env!(context.clone());
-        contract.approve(bob(), allowance.into());
+        contract.approve(bob(), allowance.into(), None);
         context.storage_usage = env::storage_usage();
         context.account_balance = env::account_balance();
 
@@ -510,9 +1130,22 @@ mod tests {
 
         // Acting as bob now
         context.is_view = false;
-        context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
+        context.attached_deposit = STORAGE_PRICE_PER_BYTE * 2000;
         context.predecessor_account_id = bob();
         testing_env!(context.clone());
+        let lock_amount = allowance / 2;
+        contract.lock(carol(), lock_amount.into());
+        assert_eq!(contract.get_locked_balance(carol(), bob()).0, lock_amount);
+        assert_eq!(
+            contract.get_unlocked_balance(carol()).0,
+            mint_balance - lock_amount
+        );
+        assert_eq!(
+            contract.get_allowance(carol(), bob()).0,
+            allowance - lock_amount
+        );
+
+        // Locked tokens are spent first.
         contract.transfer_from(carol(), alice(), transfer_amount.into());
         context.storage_usage = env::storage_usage();
         context.account_balance = env::account_balance();
@@ -525,50 +1158,1467 @@ mod tests {
             (mint_balance - transfer_amount)
         );
         assert_eq!(contract.get_balance(alice()).0, transfer_amount);
+        assert_eq!(
+            contract.get_locked_balance(carol(), bob()).0,
+            lock_amount - transfer_amount
+        );
         assert_eq!(
             contract.get_allowance(carol(), bob()).0,
-            allowance - transfer_amount
+            allowance - lock_amount
         );
     }
 
-    #[test]
-    fn test_self_allowance_set_for_refund() {
-        let mut context = get_context(tokenizer());
+    /// Mints to carol and lets bob lock half of it.
+    fn locked_by_bob(context: &mut VMContext) -> ScaleToken {
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
         testing_env!(context.clone());
-        let mint_balance = 1_000_000_000_000_000u128;
-        let mut contract = ScaleToken::new(tokenizer());
-        contract.mint_to(mint_balance, carol());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
 
         context.predecessor_account_id = carol();
         context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.approve(bob(), 50u128.into(), None);
 
-        let initial_balance = context.account_balance;
-        let initial_storage = context.storage_usage;
-        context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
+        context.predecessor_account_id = bob();
+        context.storage_usage = env::storage_usage();
         testing_env!(context.clone());
-        contract.approve(bob(), (mint_balance / 2).into());
+        contract.lock(carol(), 50u128.into());
         context.storage_usage = env::storage_usage();
-        context.account_balance = env::account_balance();
-        assert_eq!(
-            context.account_balance,
-            initial_balance
-                + Balance::from(context.storage_usage - initial_storage) * STORAGE_PRICE_PER_BYTE
-        );
+        contract
+    }
 
-        let initial_balance = context.account_balance;
-        let initial_storage = context.storage_usage;
+    #[test]
+    #[should_panic(expected = "Not enough unlocked balance")]
+    fn test_transfer_locked_tokens() {
+        let mut context = get_context(tokenizer());
+        let mut contract = locked_by_bob(&mut context);
+
+        context.predecessor_account_id = carol();
         testing_env!(context.clone());
-        context.attached_deposit = 0;
+        contract.transfer(alice(), 60u128.into());
+    }
+
+    #[test]
+    fn test_unlock() {
+        let mut context = get_context(tokenizer());
+        let mut contract = locked_by_bob(&mut context);
+        assert_eq!(contract.get_unlocked_balance(carol()).0, 50);
+
+        // Locks follow the pooled NEAR.
+        context.predecessor_account_id = tokenizer();
         testing_env!(context.clone());
-        contract.approve(bob(), (mint_balance / 2).into());
+        contract.sync_pooled_near(200u128.into());
+        assert_eq!(contract.get_locked_balance(carol(), bob()).0, 100);
+
+        context.predecessor_account_id = bob();
+        testing_env!(context.clone());
+        contract.unlock(carol(), 100u128.into());
+        assert_eq!(contract.get_locked_balance(carol(), bob()).0, 0);
+        assert_eq!(contract.get_unlocked_balance(carol()).0, 200);
+        assert_eq!(contract.get_allowance(carol(), bob()).0, 100);
+    }
+
+    #[test]
+    fn test_unlock_keeps_allowance_expiry() {
+        let mut context = get_context(tokenizer());
+        let expires_at = AllowanceExpiry::EpochHeight(5.into());
+        let mut contract = approved_until(&mut context, expires_at.clone());
+
+        context.predecessor_account_id = bob();
+        testing_env!(context.clone());
+        contract.lock(carol(), 50u128.into());
+        assert_eq!(contract.get_allowance(carol(), bob()).0, 0);
+        assert_eq!(contract.get_allowance_expiry(carol(), bob()), Some(expires_at.clone()));
+
+        contract.unlock(carol(), 20u128.into());
+        assert_eq!(contract.get_allowance(carol(), bob()).0, 20);
+        assert_eq!(contract.get_allowance_expiry(carol(), bob()), Some(expires_at));
+
+        // The expiry goes with the last lock once the allowance is spent.
+        contract.transfer_from(carol(), alice(), 50u128.into());
+        assert_eq!(contract.get_locked_balance(carol(), bob()).0, 0);
+        assert_eq!(contract.get_allowance(carol(), bob()).0, 0);
+        assert_eq!(contract.get_allowance_expiry(carol(), bob()), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough locked balance")]
+    fn test_unlock_more_than_locked() {
+        let mut context = get_context(tokenizer());
+        let mut contract = locked_by_bob(&mut context);
+        contract.unlock(carol(), 51u128.into());
+    }
+
+    #[test]
+    fn test_approve_draws_on_prepaid_storage() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mint_balance = 1_000_000_000_000_000u128;
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = STORAGE_PRICE_PER_BYTE * 1000;
+        testing_env!(context.clone());
+        contract.storage_deposit(None, None);
+        context.storage_usage = env::storage_usage();
+        let registered = contract.storage_balance_of(carol()).unwrap();
+        assert_eq!(registered.total.0, STORAGE_PRICE_PER_BYTE * 1000);
+
+        let initial_storage = context.storage_usage;
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.approve(bob(), (mint_balance / 2).into(), None);
+        context.storage_usage = env::storage_usage();
+        let approved = contract.storage_balance_of(carol()).unwrap();
+        assert_eq!(approved.total, registered.total);
+        assert_eq!(
+            approved.available.0,
+            registered.available.0
+                - Balance::from(context.storage_usage - initial_storage) * STORAGE_PRICE_PER_BYTE
+        );
+
+        // Overwriting the allowance does not use more storage.
+        testing_env!(context.clone());
+        contract.approve(bob(), (mint_balance / 3).into(), None);
+        assert_eq!(env::storage_usage(), context.storage_usage);
+        assert_eq!(contract.storage_balance_of(carol()).unwrap(), approved);
+
+        // Removing the allowance credits the storage back.
+        contract.approve(bob(), 0.into(), None);
+        assert_eq!(contract.storage_balance_of(carol()).unwrap(), registered);
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller does not have the minter role")]
+    fn test_mint_with_non_tokenizer_call() {
+        let mut context = get_context(carol());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 1_000u128.into());
+    }
+
+    #[test]
+    fn test_mint_and_burn_from_updates_total_supply() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mint_balance = 1_000_000_000_000_000u128;
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
+        assert_eq!(contract.get_total_supply().0, mint_balance);
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        let burn_amount = mint_balance / 4;
+        contract.burn_from(carol(), burn_amount.into());
+        assert_eq!(contract.get_total_supply().0, mint_balance - burn_amount);
+        assert_eq!(contract.get_balance(carol()).0, mint_balance - burn_amount);
+
+        contract.burn_from(carol(), (mint_balance - burn_amount).into());
+        assert_eq!(contract.get_total_supply().0, 0);
+        assert_eq!(contract.get_balance(carol()).0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough balance")]
+    fn test_burn_from_more_than_balance_fails() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mint_balance = 1_000_000_000_000_000u128;
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.burn_from(carol(), (mint_balance + 1).into());
+    }
+
+    #[test]
+    fn test_ft_transfer_works_on_existing_balances() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mint_balance = 1_000_000_000_000_000u128;
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.storage_deposit(None, None);
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1;
+        testing_env!(context.clone());
+        let transfer_amount = mint_balance / 3;
+        contract.ft_transfer(bob(), transfer_amount.into(), Some("memo".to_string()));
+        context.storage_usage = env::storage_usage();
+
+        context.is_view = true;
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        assert_eq!(contract.ft_total_supply().0, mint_balance);
+        assert_eq!(
+            contract.ft_balance_of(carol()).0,
+            mint_balance - transfer_amount
+        );
+        assert_eq!(contract.ft_balance_of(bob()).0, transfer_amount);
+        assert_eq!(contract.get_balance(bob()), contract.ft_balance_of(bob()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires attached deposit of exactly 1 yoctoNEAR")]
+    fn test_ft_transfer_without_deposit_fails() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mint_balance = 1_000_000_000_000_000u128;
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.ft_transfer(bob(), (mint_balance / 3).into(), None);
+    }
+
+    #[test]
+    fn test_storage_deposit_registers_account() {
+        let mut context = get_context(carol());
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        let min_balance = contract.storage_balance_bounds().min.0;
+        assert_eq!(contract.storage_balance_of(carol()), None);
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = min_balance * 2;
+        testing_env!(context.clone());
+        let balance = contract.storage_deposit(None, Some(true));
+        assert_eq!(balance.total.0, min_balance);
+        assert!(balance.available.0 <= min_balance);
+        assert_eq!(contract.storage_balance_of(carol()), Some(balance));
+    }
+
+    #[test]
+    #[should_panic(expected = "The attached deposit is less than the minimum storage balance")]
+    fn test_storage_deposit_below_minimum_fails() {
+        let mut context = get_context(carol());
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        let min_balance = contract.storage_balance_bounds().min.0;
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = min_balance - 1;
+        testing_env!(context.clone());
+        contract.storage_deposit(None, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "The attached deposit is less than the minimum storage balance")]
+    fn test_storage_deposit_registration_only_below_minimum_fails() {
+        let mut context = get_context(carol());
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        let min_balance = contract.storage_balance_bounds().min.0;
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = min_balance - 1;
+        testing_env!(context.clone());
+        contract.storage_deposit(None, Some(true));
+    }
+
+    #[test]
+    fn test_storage_withdraw_available_balance() {
+        let mut context = get_context(carol());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        let registered = contract.storage_deposit(None, None);
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1;
+        testing_env!(context.clone());
+        let withdrawn = contract.storage_withdraw(None);
+        assert_eq!(withdrawn.available.0, 0);
+        assert_eq!(
+            withdrawn.total.0,
+            registered.total.0 - registered.available.0
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't unregister the account with the positive balance without force")]
+    fn test_storage_unregister_with_positive_balance_fails() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 1_000u128.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.storage_deposit(None, None);
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1;
+        testing_env!(context.clone());
+        contract.storage_unregister(None);
+    }
+
+    #[test]
+    fn test_storage_unregister_with_force_burns_balance() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 1_000u128.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.storage_deposit(None, None);
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1;
+        testing_env!(context.clone());
+        assert!(contract.storage_unregister(Some(true)));
+        assert_eq!(contract.get_total_supply().0, 0);
+        assert_eq!(contract.storage_balance_of(carol()), None);
+        assert!(!contract.storage_unregister(None));
+    }
+
+    #[test]
+    fn test_ft_metadata_set_at_init() {
+        let context = get_context(carol());
+        testing_env!(context);
+        let contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        assert_eq!(contract.ft_metadata(), metadata());
+        assert_eq!(contract.decimals(), 18);
+    }
+
+    #[test]
+    fn test_set_ft_metadata_by_admin() {
+        let mut context = get_context(carol());
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+
+        context.predecessor_account_id = admin();
+        testing_env!(context.clone());
+        let mut new_metadata = metadata();
+        new_metadata.name = "Bond Token".to_string();
+        new_metadata.symbol = "BOND".to_string();
+        new_metadata.reference = Some("https://example.com/bond.json".to_string());
+        new_metadata.reference_hash = Some(Base64VecU8(vec![0u8; 32]));
+        contract.set_ft_metadata(new_metadata.clone());
+        assert_eq!(contract.ft_metadata(), new_metadata);
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller does not have the metadata_manager role")]
+    fn test_set_ft_metadata_with_non_admin_call() {
+        let context = get_context(carol());
+        testing_env!(context);
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.set_ft_metadata(metadata());
+    }
+
+    #[test]
+    #[should_panic(expected = "Decimals can't be changed")]
+    fn test_set_ft_metadata_can_not_change_decimals() {
+        let mut context = get_context(carol());
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+
+        context.predecessor_account_id = admin();
+        testing_env!(context.clone());
+        let mut new_metadata = metadata();
+        new_metadata.decimals = 24;
+        contract.set_ft_metadata(new_metadata);
+    }
+
+    #[test]
+    fn test_balances_follow_pooled_near() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+        contract.mint(bob(), 300u128.into());
+        assert_eq!(contract.get_shares(carol()).0, 100);
+        assert_eq!(contract.get_shares(bob()).0, 300);
+
+        // Staking rewards doubled the pool.
+        contract.sync_pooled_near(800u128.into());
+        assert_eq!(contract.get_total_supply().0, 800);
+        assert_eq!(contract.get_total_shares().0, 400);
+        assert_eq!(contract.get_balance(carol()).0, 200);
+        assert_eq!(contract.get_balance(bob()).0, 600);
+        assert_eq!(contract.get_pooled_near_by_shares(50u128.into()).0, 100);
+        assert_eq!(contract.get_shares_by_pooled_near(100u128.into()).0, 50);
+
+        // New deposits get shares at the current rate.
+        contract.mint(alice(), 200u128.into());
+        assert_eq!(contract.get_shares(alice()).0, 100);
+        assert_eq!(contract.get_balance(alice()).0, 200);
+
+        contract.burn_from(bob(), 600u128.into());
+        assert_eq!(contract.get_shares(bob()).0, 0);
+        assert_eq!(contract.get_total_supply().0, 400);
+        assert_eq!(contract.get_total_shares().0, 200);
+    }
+
+    #[test]
+    fn test_transfer_moves_shares() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.sync_pooled_near(300u128.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        contract.transfer(bob(), 150u128.into());
+        assert_eq!(contract.get_shares(carol()).0, 50);
+        assert_eq!(contract.get_shares(bob()).0, 50);
+        assert_eq!(contract.get_balance(carol()).0, 150);
+        assert_eq!(contract.get_balance(bob()).0, 150);
+    }
+
+    #[test]
+    fn test_batch_transfer() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 1_000u128.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 2000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        contract.batch_transfer(
+            vec![(alice(), 100u128.into()), (bob(), 200u128.into())],
+            Some("referral".to_string()),
+        );
+        assert_eq!(contract.get_balance(carol()).0, 700);
+        assert_eq!(contract.get_balance(alice()).0, 100);
+        assert_eq!(contract.get_balance(bob()).0, 200);
+        assert_eq!(contract.get_total_supply().0, 1_000);
+        assert_eq!(contract.get_holder_count(), 3);
+        assert_eq!(contract.get_storage_payer(alice()), Some(carol()));
+        assert_eq!(contract.get_storage_payer(bob()), Some(carol()));
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough balance")]
+    fn test_batch_transfer_more_than_balance() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.batch_transfer(vec![(alice(), 60u128.into()), (bob(), 60u128.into())], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "The receiver alice.near is listed more than once")]
+    fn test_batch_transfer_to_duplicate_receiver() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.batch_transfer(vec![(alice(), 10u128.into()), (alice(), 10u128.into())], None);
+    }
+
+    #[test]
+    #[should_panic(expected = "the maximum is 2")]
+    fn test_batch_transfer_with_not_enough_gas() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        context.prepaid_gas = 20_000_000_000_000;
+        testing_env!(context.clone());
+        contract.batch_transfer(
+            vec![
+                (alice(), 10u128.into()),
+                (bob(), 10u128.into()),
+                (tokenizer(), 10u128.into()),
+            ],
+            None,
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller does not have the minter role")]
+    fn test_sync_pooled_near_with_non_tokenizer_call() {
+        let context = get_context(carol());
+        testing_env!(context);
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.sync_pooled_near(1u128.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't sync zero pooled NEAR while there are shares")]
+    fn test_sync_zero_pooled_near_with_shares_fails() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.sync_pooled_near(0u128.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "The pooled NEAR has no shares")]
+    fn test_mint_into_pooled_near_without_shares_fails() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.sync_pooled_near(100u128.into());
+
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.mint(carol(), 100u128.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Converting between shares and pooled NEAR overflows u128")]
+    fn test_mint_with_share_overflow_fails() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+        contract.sync_pooled_near(1u128.into());
+
+        // 100 shares per yocto, u128::MAX yocto would be worth more shares than fit in u128.
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.mint(bob(), u128::MAX.into());
+    }
+
+    #[test]
+    fn test_mint_after_pooled_near_synced_to_zero() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.sync_pooled_near(100u128.into());
+        contract.sync_pooled_near(0u128.into());
+
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.mint(carol(), 100u128.into());
+        assert_eq!(contract.get_balance(carol()).0, 100);
+        assert_eq!(contract.get_total_supply().0, 100);
+    }
+
+    #[test]
+    fn test_initial_roles() {
+        let context = get_context(carol());
+        testing_env!(context);
+        let contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        assert!(contract.has_role(Role::Admin, admin()));
+        assert!(contract.has_role(Role::MetadataManager, admin()));
+        assert!(contract.has_role(Role::Minter, tokenizer()));
+        assert!(contract.has_role(Role::Burner, tokenizer()));
+        assert!(!contract.has_role(Role::Minter, admin()));
+        assert!(contract.get_role_members(Role::Pauser).is_empty());
+    }
+
+    #[test]
+    fn test_backup_minter() {
+        let mut context = get_context(admin());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        assert!(contract.grant_role(Role::Minter, bob()));
+        assert!(!contract.grant_role(Role::Minter, bob()));
+        assert_eq!(contract.get_role_members(Role::Minter), vec![tokenizer(), bob()]);
+
+        context.predecessor_account_id = bob();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.mint(carol(), 100u128.into());
+        assert_eq!(contract.get_balance(carol()).0, 100);
+
+        context.predecessor_account_id = admin();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        assert!(contract.revoke_role(Role::Minter, bob()));
+        assert!(!contract.has_role(Role::Minter, bob()));
+        assert_eq!(contract.get_role_members(Role::Minter), vec![tokenizer()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller does not have the minter role")]
+    fn test_mint_after_renounce_role() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        assert!(contract.renounce_role(Role::Minter));
+        contract.mint(carol(), 100u128.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller does not have the admin role")]
+    fn test_grant_role_with_non_admin_call() {
+        let context = get_context(tokenizer());
+        testing_env!(context);
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.grant_role(Role::Minter, bob());
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't remove the last admin")]
+    fn test_renounce_last_admin() {
+        let context = get_context(admin());
+        testing_env!(context);
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.renounce_role(Role::Admin);
+    }
+
+    fn paused_contract(context: &mut VMContext) -> ScaleToken {
+        context.predecessor_account_id = tokenizer();
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+
+        context.predecessor_account_id = admin();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.grant_role(Role::Pauser, bob());
+
+        context.predecessor_account_id = bob();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.pause();
+        contract
+    }
+
+    #[test]
+    fn test_pause_and_unpause() {
+        let mut context = get_context(carol());
+        let mut contract = paused_contract(&mut context);
+        assert!(contract.is_paused());
+        // Views keep working.
+        assert_eq!(contract.get_balance(carol()).0, 100);
+        assert_eq!(contract.ft_total_supply().0, 100);
+
+        context.predecessor_account_id = admin();
+        testing_env!(context.clone());
+        contract.unpause();
+        assert!(!contract.is_paused());
+
+        context.predecessor_account_id = carol();
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        contract.transfer(alice(), 40u128.into());
+        assert_eq!(contract.get_balance(alice()).0, 40);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is paused")]
+    fn test_transfer_when_paused() {
+        let mut context = get_context(carol());
+        let mut contract = paused_contract(&mut context);
+        context.predecessor_account_id = carol();
+        testing_env!(context.clone());
+        contract.transfer(alice(), 40u128.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is paused")]
+    fn test_approve_when_paused() {
+        let mut context = get_context(carol());
+        let mut contract = paused_contract(&mut context);
+        context.predecessor_account_id = carol();
+        testing_env!(context.clone());
+        contract.approve(alice(), 40u128.into(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "Token is paused")]
+    fn test_mint_when_paused() {
+        let mut context = get_context(carol());
+        let mut contract = paused_contract(&mut context);
+        context.predecessor_account_id = tokenizer();
+        testing_env!(context.clone());
+        contract.mint(carol(), 100u128.into());
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller does not have the admin role")]
+    fn test_unpause_by_pauser() {
+        let mut context = get_context(carol());
+        let mut contract = paused_contract(&mut context);
+        contract.unpause();
+    }
+
+    #[test]
+    fn test_rotate_tokenizer() {
+        let mut context = get_context(admin());
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.propose_tokenizer(bob());
+        assert_eq!(contract.get_pending_tokenizer(), Some(bob()));
+        assert_eq!(contract.get_tokenizer(), tokenizer());
+
+        context.predecessor_account_id = bob();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        contract.accept_tokenizer();
+        assert_eq!(contract.get_tokenizer(), bob());
+        assert_eq!(contract.get_pending_tokenizer(), None);
+        assert!(contract.has_role(Role::Minter, bob()));
+        assert!(contract.has_role(Role::Burner, bob()));
+        assert!(!contract.has_role(Role::Minter, tokenizer()));
+        assert!(!contract.has_role(Role::Burner, tokenizer()));
+
+        contract.mint(carol(), 100u128.into());
+        assert_eq!(contract.get_balance(carol()).0, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller is not the pending Tokenizer")]
+    fn test_accept_tokenizer_after_cancel() {
+        let mut context = get_context(admin());
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.propose_tokenizer(bob());
+        contract.cancel_tokenizer_proposal();
+        assert_eq!(contract.get_pending_tokenizer(), None);
+
+        context.predecessor_account_id = bob();
+        testing_env!(context.clone());
+        contract.accept_tokenizer();
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller does not have the admin role")]
+    fn test_propose_tokenizer_with_non_admin_call() {
+        let context = get_context(tokenizer());
+        testing_env!(context);
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.propose_tokenizer(bob());
+    }
+
+    /// Writes the v1 state byte by byte, carol holds 100 tokens and allows bob to spend 40.
+    fn write_v1_state() {
+        let carol_hash = env::sha256(carol().as_bytes());
+        // ScaleToken { accounts: LookupMap(b"a"), total_supply, decimals, tokenizer }
+        let mut state = b"a".to_vec().try_to_vec().unwrap();
+        state.extend(100u128.try_to_vec().unwrap());
+        state.extend(18u8.try_to_vec().unwrap());
+        state.extend(tokenizer().try_to_vec().unwrap());
+        env::storage_write(b"STATE", &state);
+        // Account { balance, allowances: LookupMap(sha256(AccountID)), allowances_count }
+        let mut account = 100u128.try_to_vec().unwrap();
+        account.extend(carol_hash.try_to_vec().unwrap());
+        account.extend(1u32.try_to_vec().unwrap());
+        env::storage_write(&[b"a".to_vec(), carol_hash.clone()].concat(), &account);
+        let allowance_key = [carol_hash, env::sha256(bob().as_bytes())].concat();
+        env::storage_write(&allowance_key, &40u128.try_to_vec().unwrap());
+    }
+
+    fn migrate_v1_state(context: &mut VMContext) -> ScaleToken {
+        testing_env!(context.clone());
+        write_v1_state();
+        context.predecessor_account_id = alice();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        ScaleToken::migrate()
+    }
+
+    #[test]
+    fn test_migrate_v1_state() {
+        let mut context = get_context(carol());
+        let mut contract = migrate_v1_state(&mut context);
+        assert_eq!(contract.get_state_version(), STATE_VERSION);
+        assert_eq!(contract.get_balance(carol()).0, 100);
+        assert_eq!(contract.get_shares(carol()).0, 100);
+        assert_eq!(contract.get_total_supply().0, 100);
+        assert_eq!(contract.get_total_shares().0, 100);
+        assert_eq!(contract.decimals(), 18);
+        assert!(contract.has_role(Role::Minter, tokenizer()));
+        assert!(contract.has_role(Role::Burner, tokenizer()));
+        // The account of the contract deployed the upgrade.
+        assert!(contract.has_role(Role::Admin, alice()));
+
+        // The next update of carol moves her balance, the record stays for the allowance.
+        let carol_hash = env::sha256(carol().as_bytes());
+        context.predecessor_account_id = carol();
         context.storage_usage = env::storage_usage();
-        context.account_balance = env::account_balance();
-        assert!(context.storage_usage == initial_storage);
-        assert!(context.account_balance == initial_balance);
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        contract.transfer(bob(), 30u128.into());
+        assert_eq!(contract.get_balance(carol()).0, 70);
+        assert_eq!(contract.get_balance(bob()).0, 30);
+        assert_eq!(contract.get_holders(0, 10), vec![carol(), bob()]);
+        assert!(contract.accounts.get(&carol_hash).is_some());
+        let legacy_account = contract.legacy_accounts.get(&carol_hash).unwrap();
+        assert_eq!(legacy_account.balance, 0);
+        assert_eq!(legacy_account.allowances_count, 1);
+        assert_eq!(legacy_account.allowances.get(&env::sha256(bob().as_bytes())), Some(40));
+    }
+
+    #[test]
+    fn test_spend_v1_allowance() {
+        let mut context = get_context(carol());
+        let mut contract = migrate_v1_state(&mut context);
+        assert_eq!(contract.get_allowance(carol(), bob()).0, 40);
+        assert!(contract.get_allowances(carol(), 0, 10).is_empty());
+
+        // Spending moves the allowance to the current keys.
+        let carol_hash = env::sha256(carol().as_bytes());
+        context.predecessor_account_id = bob();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        contract.transfer_from(carol(), bob(), 30u128.into());
+        assert_eq!(contract.get_allowance(carol(), bob()).0, 10);
+        assert_eq!(contract.get_allowances(carol(), 0, 10), vec![(bob(), 10u128.into())]);
+        assert_eq!(contract.get_balance(carol()).0, 70);
+        assert!(contract.legacy_accounts.get(&carol_hash).is_none());
+    }
+
+    #[test]
+    fn test_dec_v1_allowance_to_zero() {
+        let mut context = get_context(carol());
+        let mut contract = migrate_v1_state(&mut context);
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        contract.dec_allowance(bob(), 40u128.into());
+        assert_eq!(contract.get_allowance(carol(), bob()).0, 0);
+        assert_eq!(contract.revoke_all_allowances(), 0);
+        let carol_hash = env::sha256(carol().as_bytes());
+        assert!(contract.legacy_accounts.get(&carol_hash).is_none());
+        assert_eq!(contract.get_balance(carol()).0, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "1 allowances granted before the upgrade are left")]
+    fn test_revoke_all_allowances_with_v1_allowances_fails() {
+        let mut context = get_context(carol());
+        let mut contract = migrate_v1_state(&mut context);
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.revoke_all_allowances();
+    }
+
+    #[test]
+    fn test_migrate_accounts() {
+        let mut context = get_context(carol());
+        let mut contract = migrate_v1_state(&mut context);
+
+        // v1 has no admin, the account of the contract is the admin after the upgrade.
+        context.predecessor_account_id = alice();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        assert_eq!(
+            contract.migrate_accounts(vec![(carol(), vec![alice(), bob()]), (bob(), vec![])]),
+            1
+        );
+        assert_eq!(contract.migrate_accounts(vec![(carol(), vec![bob()])]), 0);
+        let carol_hash = env::sha256(carol().as_bytes());
+        assert!(contract.legacy_accounts.get(&carol_hash).is_none());
+        assert_eq!(contract.get_allowances(carol(), 0, 10), vec![(bob(), 40u128.into())]);
+        assert_eq!(contract.get_balance(carol()).0, 100);
+        assert_eq!(contract.get_holders(0, 10), vec![carol()]);
+    }
+
+    #[test]
+    fn test_holder_count_after_migrate_accounts() {
+        let mut context = get_context(carol());
+        let mut contract = migrate_v1_state(&mut context);
+        // Carol holds tokens in v1 but is not moved yet.
+        assert_eq!(contract.get_holder_count(), 0);
+
+        context.predecessor_account_id = alice();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        assert_eq!(contract.migrate_accounts(vec![(carol(), vec![bob()])]), 1);
+        assert_eq!(contract.get_holder_count(), 1);
+        assert_eq!(contract.get_holders(0, 10), vec![carol()]);
+
+        // Migrating again does not count her twice.
+        assert_eq!(contract.migrate_accounts(vec![(carol(), vec![bob()])]), 0);
+        assert_eq!(contract.get_holder_count(), 1);
+    }
+
+    #[test]
+    fn test_migrate_accounts_without_escrows_keeps_allowances() {
+        let mut context = get_context(carol());
+        let mut contract = migrate_v1_state(&mut context);
+
+        context.predecessor_account_id = alice();
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        assert_eq!(contract.migrate_accounts(vec![(carol(), vec![])]), 1);
+        assert_eq!(contract.get_balance(carol()).0, 100);
+        assert_eq!(contract.get_allowance(carol(), bob()).0, 40);
+        assert_eq!(contract.migrate_accounts(vec![(carol(), vec![bob()])]), 1);
+        assert_eq!(contract.get_allowances(carol(), 0, 10), vec![(bob(), 40u128.into())]);
+        assert_eq!(contract.get_balance(carol()).0, 100);
+    }
+
+    #[test]
+    fn test_migrate_current_state() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+        env::state_write(&contract);
+
+        context.predecessor_account_id = alice();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        let migrated = ScaleToken::migrate();
+        assert_eq!(migrated.get_state_version(), STATE_VERSION);
+        assert_eq!(migrated.get_balance(carol()).0, 100);
+        assert_eq!(migrated.get_holders(0, 10), vec![carol()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Method migrate is private")]
+    fn test_migrate_with_non_self_call() {
+        let context = get_context(carol());
+        testing_env!(context);
+        let contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        env::state_write(&contract);
+        ScaleToken::migrate();
+    }
+
+    #[test]
+    #[should_panic(expected = "Caller does not have the admin role")]
+    fn test_upgrade_with_non_admin_call() {
+        let context = get_context(tokenizer());
+        testing_env!(context);
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.upgrade(Base64VecU8(vec![0u8; 8]));
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let error = TokenError::NotEnoughBalance;
+        assert_eq!(error.code(), 313);
+        assert_eq!(error.to_string(), "Not enough balance (E313)");
+        assert_eq!(TokenError::parse_code(&error.to_string()), Some(313));
+        assert_eq!(
+            TokenError::MissingRole(Role::Minter).to_string(),
+            "Caller does not have the minter role (E200)"
+        );
+        assert_eq!(
+            TokenError::parse_code("Smart contract panicked: The account carol.near is not registered (E500)"),
+            Some(500)
+        );
+        assert_eq!(TokenError::parse_code("Not enough balance"), None);
+    }
+
+    #[test]
+    fn test_error_codes_are_unique() {
+        // Every variant, in declaration order.
+        let errors = vec![
+            TokenError::AlreadyInitialized,
+            TokenError::NotInitialized,
+            TokenError::UnsupportedStateVersion(0),
+            TokenError::InvalidStateVersion,
+            TokenError::PrivateMethod("migrate"),
+            TokenError::MissingRole(Role::Admin),
+            TokenError::LastAdmin,
+            TokenError::NotPendingTokenizer,
+            TokenError::NoPendingTokenizer,
+            TokenError::SameTokenizer,
+            TokenError::Paused,
+            TokenError::AlreadyPaused,
+            TokenError::NotPaused,
+            TokenError::InvalidAccountId,
+            TokenError::InvalidOwnerAccountId,
+            TokenError::InvalidNewOwnerAccountId,
+            TokenError::InvalidEscrowAccountId,
+            TokenError::InvalidTokenizerAccountId,
+            TokenError::ZeroTransfer,
+            TokenError::SelfTransfer,
+            TokenError::TransferTooSmall,
+            TokenError::NotEnoughBalance,
+            TokenError::BalanceOverflow,
+            TokenError::EmptyBatch,
+            TokenError::BatchTooLong(0),
+            TokenError::DuplicateReceiver(bob()),
+            TokenError::NotEnoughAllowance,
+            TokenError::SelfAllowanceIncrement,
+            TokenError::SelfAllowanceDecrement,
+            TokenError::AllowanceExpiryInPast,
+            TokenError::LegacyAllowancesLeft(0),
+            TokenError::RequiresOneYocto,
+            TokenError::NotEnoughGas,
+            TokenError::ZeroLock,
+            TokenError::LockTooSmall,
+            TokenError::NotEnoughUnlockedBalance,
+            TokenError::NotEnoughLockedBalance,
+            TokenError::ZeroMint,
+            TokenError::MintTooSmall,
+            TokenError::ZeroBurn,
+            TokenError::TotalSupplyOverflow,
+            TokenError::TotalSharesOverflow,
+            TokenError::TotalSupplyUnderflow,
+            TokenError::ZeroPooledNear,
+            TokenError::PooledNearWithoutShares,
+            TokenError::ConversionOverflow,
+            TokenError::NotRegistered(bob()),
+            TokenError::DepositBelowMinimum(0),
+            TokenError::InsufficientStorageBalance { required: 0, balance: 0 },
+            TokenError::WithdrawExceedsAvailable,
+            TokenError::UnregisterWithAllowances,
+            TokenError::UnregisterWithBalance,
+            TokenError::UnregisterWithLockedBalance,
+            TokenError::UnregisterWithStorageCredit,
+            TokenError::UnsupportedMetadataSpec,
+            TokenError::EmptyMetadataName,
+            TokenError::EmptyMetadataSymbol,
+            TokenError::MetadataReferenceMismatch,
+            TokenError::InvalidMetadataReferenceHash,
+            TokenError::DecimalsChanged,
+        ];
+        // Codes increase with the declaration order, so none is used twice.
+        let codes: Vec<u16> = errors.iter().map(|error| error.code()).collect();
+        for pair in codes.windows(2) {
+            assert!(pair[0] < pair[1], "code {} is not below {}", pair[0], pair[1]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough balance (E313)")]
+    fn test_panic_message_has_error_code() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.transfer(bob(), 200u128.into());
+    }
+
+    #[test]
+    fn test_get_allowances_and_revoke_all() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mint_balance = 1_000_000_000_000_000u128;
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.storage_deposit(None, None);
+        context.storage_usage = env::storage_usage();
+        let registered = contract.storage_balance_of(carol()).unwrap();
+
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.approve(bob(), 10u128.into(), None);
+        contract.approve(alice(), 20u128.into(), None);
+        contract.approve(tokenizer(), 30u128.into(), None);
+        assert_eq!(
+            contract.get_allowances(carol(), 0, 10),
+            vec![
+                (bob(), 10u128.into()),
+                (alice(), 20u128.into()),
+                (tokenizer(), 30u128.into())
+            ]
+        );
+        assert_eq!(
+            contract.get_allowances(carol(), 1, 1),
+            vec![(alice(), 20u128.into())]
+        );
+
+        assert_eq!(contract.revoke_all_allowances(), 3);
+        assert_eq!(contract.get_allowances(carol(), 0, 10), vec![]);
+        assert_eq!(contract.get_allowance(carol(), alice()).0, 0);
+        assert_eq!(env::storage_usage(), context.storage_usage);
+        assert_eq!(contract.storage_balance_of(carol()).unwrap(), registered);
+    }
+
+    /// Mints to carol and lets her approve bob until the given expiry.
+    fn approved_until(context: &mut VMContext, expires_at: AllowanceExpiry) -> ScaleToken {
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.approve(bob(), 50u128.into(), Some(expires_at));
+        context.storage_usage = env::storage_usage();
+        contract
+    }
+
+    #[test]
+    fn test_allowance_expires_at_timestamp() {
+        let mut context = get_context(tokenizer());
+        let expires_at = AllowanceExpiry::Timestamp(1_000.into());
+        let mut contract = approved_until(&mut context, expires_at.clone());
+        let approved = contract.storage_balance_of(carol()).unwrap();
+        assert_eq!(contract.get_allowance(carol(), bob()).0, 50);
+        assert_eq!(contract.get_allowance_expiry(carol(), bob()), Some(expires_at));
+
+        context.block_timestamp = 1_000;
+        context.predecessor_account_id = alice();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        assert_eq!(contract.get_allowance(carol(), bob()).0, 0);
+        assert_eq!(contract.get_allowances(carol(), 0, 10), vec![(bob(), 0u128.into())]);
+
+        // Anyone can remove it, the storage is credited to carol.
+        assert_eq!(contract.remove_expired_allowances(carol(), vec![bob(), alice()]), 1);
+        assert_eq!(contract.get_allowances(carol(), 0, 10), vec![]);
+        assert_eq!(contract.get_allowance_expiry(carol(), bob()), None);
+        assert!(contract.storage_balance_of(carol()).unwrap().available.0 > approved.available.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough allowance")]
+    fn test_transfer_from_with_expired_allowance() {
+        let mut context = get_context(tokenizer());
+        let mut contract = approved_until(&mut context, AllowanceExpiry::EpochHeight(5.into()));
+
+        context.epoch_height = 5;
+        context.predecessor_account_id = bob();
+        testing_env!(context.clone());
+        contract.transfer_from(carol(), alice(), 10u128.into());
+    }
+
+    #[test]
+    fn test_dec_allowance_removes_expired_allowance() {
+        let mut context = get_context(tokenizer());
+        let mut contract = approved_until(&mut context, AllowanceExpiry::EpochHeight(5.into()));
+
+        // Spending part of the allowance keeps the expiry.
+        context.predecessor_account_id = bob();
+        context.attached_deposit = 2000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        contract.transfer_from(carol(), alice(), 10u128.into());
+        assert_eq!(contract.get_allowance(carol(), bob()).0, 40);
+        assert!(contract.get_allowance_expiry(carol(), bob()).is_some());
+
+        context.epoch_height = 5;
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.dec_allowance(bob(), 1u128.into());
+        assert_eq!(contract.get_allowances(carol(), 0, 10), vec![]);
+        assert_eq!(contract.get_allowance_expiry(carol(), bob()), None);
+    }
+
+    #[test]
+    fn test_inc_allowance_removes_expired_allowance() {
+        let mut context = get_context(tokenizer());
+        let mut contract = approved_until(&mut context, AllowanceExpiry::EpochHeight(5.into()));
+        let approved = contract.storage_balance_of(carol()).unwrap();
+
+        context.epoch_height = 5;
+        context.predecessor_account_id = carol();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.inc_allowance(bob(), 10u128.into());
+        assert_eq!(contract.get_allowances(carol(), 0, 10), vec![(bob(), 10u128.into())]);
+        assert_eq!(contract.get_allowance_expiry(carol(), bob()), None);
+        // The storage of the expiry is credited to carol.
+        assert!(env::storage_usage() < context.storage_usage);
+        assert!(contract.storage_balance_of(carol()).unwrap().available.0 > approved.available.0);
+
+        // The expired entry is gone, nothing is left to remove.
+        assert_eq!(contract.remove_expired_allowances(carol(), vec![bob()]), 0);
+        context.predecessor_account_id = bob();
+        context.attached_deposit = 2000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        contract.transfer_from(carol(), alice(), 10u128.into());
+        assert_eq!(contract.get_allowances(carol(), 0, 10), vec![]);
+        assert_eq!(contract.get_balance(alice()).0, 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "Allowance expiry is in the past")]
+    fn test_approve_with_past_expiry() {
+        let mut context = get_context(tokenizer());
+        context.block_timestamp = 1_000;
+        approved_until(&mut context, AllowanceExpiry::Timestamp(1_000.into()));
+    }
+
+    #[test]
+    fn test_mint_records_storage_payer() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+        assert_eq!(contract.get_storage_payer(carol()), Some(tokenizer()));
+        let credit = contract.get_storage_credit(tokenizer()).0;
+        assert!(credit > 0);
+        let minted = contract.storage_balance_of(tokenizer()).unwrap();
+
+        // The Tokenizer keeps paying for carol after she registers, until she unregisters.
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.storage_deposit(None, None);
+        assert_eq!(contract.get_storage_payer(carol()), Some(tokenizer()));
+        assert_eq!(contract.get_storage_credit(tokenizer()).0, credit);
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1;
+        testing_env!(context.clone());
+        contract.storage_unregister(Some(true));
+        assert_eq!(contract.get_storage_payer(carol()), None);
+        assert_eq!(contract.get_storage_credit(tokenizer()).0, 0);
+        // The credit entry of the Tokenizer is freed as well.
+        assert!(
+            contract.storage_balance_of(tokenizer()).unwrap().available.0
+                > minted.available.0 + credit
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Can't unregister the account while it pays for the storage")]
+    fn test_storage_unregister_with_storage_credit() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+        assert!(contract.get_storage_credit(tokenizer()).0 > 0);
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 1;
+        testing_env!(context.clone());
+        contract.storage_unregister(None);
+    }
+
+    #[test]
+    fn test_burn_credits_storage_payer() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+        let credit = contract.get_storage_credit(tokenizer()).0;
+        let minted = contract.storage_balance_of(tokenizer()).unwrap();
+
+        context.storage_usage = env::storage_usage();
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.burn_from(carol(), 100u128.into());
+        assert_eq!(contract.get_storage_payer(carol()), None);
+        assert_eq!(contract.get_storage_credit(tokenizer()).0, 0);
+        assert!(
+            contract.storage_balance_of(tokenizer()).unwrap().available.0
+                > minted.available.0 + credit
+        );
+    }
+
+    #[test]
+    fn test_escrow_drain_credits_payers() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mint_balance = 1_000_000_000_000_000u128;
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), mint_balance.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.storage_deposit(None, None);
+        context.storage_usage = env::storage_usage();
+        let registered = contract.storage_balance_of(carol()).unwrap();
+
+        context.attached_deposit = 0;
+        testing_env!(context.clone());
+        contract.approve(bob(), mint_balance.into(), None);
+        context.storage_usage = env::storage_usage();
+        assert!(contract.get_storage_credit(carol()).0 > 0);
+        let minted = contract.get_storage_credit(tokenizer()).0;
+
+        // Bob drains carol. The freed allowance is credited to carol, her registered record
+        // stays paid by the Tokenizer.
+        context.predecessor_account_id = bob();
+        context.attached_deposit = 2000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        contract.transfer_from(carol(), alice(), mint_balance.into());
+        assert_eq!(contract.get_balance(carol()).0, 0);
+        assert_eq!(contract.get_storage_credit(carol()).0, 0);
+        assert_eq!(contract.storage_balance_of(carol()).unwrap(), registered);
+        assert_eq!(contract.get_storage_credit(tokenizer()).0, minted);
+        assert_eq!(contract.get_storage_payer(alice()), Some(bob()));
+        assert!(contract.get_storage_credit(bob()).0 > 0);
+    }
+
+    #[test]
+    fn test_storage_payer_is_recorded_on_creation_only() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+        let minted = contract.get_storage_credit(tokenizer()).0;
+
+        // Carol registers and empties her balance, her record stays paid by the Tokenizer.
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.storage_deposit(None, None);
+        contract.transfer(bob(), 100u128.into());
+        assert_eq!(contract.get_holders(0, 10), vec![bob()]);
+        assert_eq!(contract.get_storage_payer(bob()), Some(carol()));
+        let transferred = contract.get_storage_credit(carol()).0;
+
+        // Bob adds her back to the holders, the growth is not attributed to the Tokenizer.
+        context.predecessor_account_id = bob();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.transfer(carol(), 50u128.into());
+        assert_eq!(contract.get_holder_count(), 2);
+        assert_eq!(contract.get_storage_payer(carol()), Some(tokenizer()));
+        assert_eq!(contract.get_storage_credit(tokenizer()).0, minted);
+        assert_eq!(contract.get_storage_credit(carol()).0, transferred);
+        assert_eq!(contract.get_storage_credit(bob()).0, 0);
+    }
+
+    #[test]
+    fn test_holders_follow_balances() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+        contract.mint(bob(), 100u128.into());
+        assert_eq!(contract.get_holder_count(), 2);
+        assert_eq!(contract.get_holders(0, 10), vec![carol(), bob()]);
+        assert_eq!(contract.get_holders(1, 10), vec![bob()]);
+        assert!(contract.get_holders(2, 10).is_empty());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        testing_env!(context.clone());
+        contract.transfer(alice(), 100u128.into());
+        assert_eq!(contract.get_holder_count(), 2);
+        assert_eq!(contract.get_holders(0, 10), vec![bob(), alice()]);
+    }
+
+    #[test]
+    fn test_balance_and_total_supply_at_epoch() {
+        let mut context = get_context(tokenizer());
+        context.attached_deposit = 1000 * STORAGE_PRICE_PER_BYTE;
+        context.epoch_height = 10;
+        testing_env!(context.clone());
+        let mut contract = ScaleToken::new(tokenizer(), admin(), metadata());
+        contract.mint(carol(), 100u128.into());
+        // Changes in the same epoch overwrite the checkpoint.
+        contract.mint(carol(), 100u128.into());
+
+        context.storage_usage = env::storage_usage();
+        context.epoch_height = 12;
+        testing_env!(context.clone());
+        contract.sync_pooled_near(400u128.into());
+
+        context.predecessor_account_id = carol();
+        context.storage_usage = env::storage_usage();
+        context.epoch_height = 15;
+        testing_env!(context.clone());
+        contract.transfer(bob(), 100u128.into());
+
+        assert_eq!(contract.get_total_supply_at(9).0, 0);
+        assert_eq!(contract.get_total_supply_at(10).0, 200);
+        assert_eq!(contract.get_total_supply_at(11).0, 200);
+        assert_eq!(contract.get_total_supply_at(12).0, 400);
+        assert_eq!(contract.get_total_supply_at(20).0, 400);
+
+        assert_eq!(contract.get_balance_at(carol(), 9).0, 0);
+        assert_eq!(contract.get_balance_at(carol(), 10).0, 200);
+        assert_eq!(contract.get_balance_at(carol(), 12).0, 400);
+        assert_eq!(contract.get_balance_at(carol(), 15).0, 300);
+        assert_eq!(contract.get_balance_at(bob(), 14).0, 0);
+        assert_eq!(contract.get_balance_at(bob(), 15).0, 100);
+    }
+
+    #[test]
+    fn test_event_json_format() {
+        let old_owner_id = carol();
+        let new_owner_id = bob();
+        let log = FtTransfer::to_log(&[FtTransfer {
+            old_owner_id: &old_owner_id,
+            new_owner_id: &new_owner_id,
+            amount: 100u128.into(),
+            memo: None,
+        }]);
+        assert_eq!(
+            log,
+            r#"EVENT_JSON:{"standard":"nep141","version":"1.0.0","event":"ft_transfer","data":[{"old_owner_id":"carol.near","new_owner_id":"bob.near","amount":"100"}]}"#
+        );
+
+        let log = Approve::to_log(&[Approve {
+            owner_id: &old_owner_id,
+            escrow_account_id: &new_owner_id,
+            amount: 5u128.into(),
+            expires_at: None,
+        }]);
         assert_eq!(
-            context.account_balance,
-            initial_balance
-                - Balance::from(initial_storage - context.storage_usage) * STORAGE_PRICE_PER_BYTE
+            log,
+            r#"EVENT_JSON:{"standard":"scale_token","version":"1.0.0","event":"approve","data":[{"owner_id":"carol.near","escrow_account_id":"bob.near","amount":"5"}]}"#
         );
     }
 }
\ No newline at end of file
@@ -0,0 +1,212 @@
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId, Balance};
+
+use crate::events::{Approve, Event, Lock, Unlock};
+use crate::{escrow_key, Account, ScaleToken, TokenError};
+
+/// NEP-21 style locks. An escrow locks part of the tokens of an owner against its allowance,
+/// locked tokens can only be moved by that escrow. Locks are kept in shares, so locked
+/// tokens follow the pooled NEAR like the rest of the balance.
+#[near_bindgen]
+impl ScaleToken {
+    /// Locks `amount` of the tokens of `owner_id` for the caller, spending the allowance of
+    /// the caller. Storage of the lock is paid from the prepaid storage balance of the caller.
+    #[payable]
+    pub fn lock(&mut self, owner_id: AccountId, amount: U128) {
+        self.assert_not_paused();
+        self.deposit_attached_storage();
+        let initial_storage = env::storage_usage();
+        let escrow_account_id = env::predecessor_account_id();
+        let amount: Balance = amount.into();
+        if amount == 0 {
+            TokenError::ZeroLock.panic();
+        }
+        let shares = self.amount_to_shares(amount);
+        if shares == 0 {
+            TokenError::LockTooSmall.panic();
+        }
+        let account = self.get_account(&owner_id);
+        self.assert_unlocked_shares(&owner_id, &account, shares);
+        // Locked first, so the expiry of a spent allowance is kept for `unlock`.
+        self.increase_locked_shares(&owner_id, &escrow_account_id, shares);
+        self.spend_allowance(&owner_id, &escrow_account_id, amount);
+        self.settle_storage(&escrow_account_id, initial_storage);
+        Lock {
+            owner_id: &owner_id,
+            escrow_account_id: &escrow_account_id,
+            amount: amount.into(),
+        }
+        .emit();
+    }
+
+    /// Unlocks `amount` of the tokens of `owner_id` locked by the caller. The tokens go back
+    /// to the unlocked balance of the owner and the allowance of the caller grows by `amount`,
+    /// saturating at `u128::MAX`. The expiry of the allowance is kept. Storage of a restored
+    /// allowance is paid from the storage balance of the owner.
+    pub fn unlock(&mut self, owner_id: AccountId, amount: U128) {
+        let escrow_account_id = env::predecessor_account_id();
+        let amount: Balance = amount.into();
+        if amount == 0 {
+            TokenError::ZeroLock.panic();
+        }
+        let shares = self.locked_shares_of_amount(&owner_id, &escrow_account_id, amount);
+        let initial_storage = env::storage_usage();
+        self.restore_allowance(&owner_id, &escrow_account_id, amount);
+        self.settle_storage(&owner_id, initial_storage);
+
+        let initial_storage = env::storage_usage();
+        self.decrease_locked_shares(&owner_id, &escrow_account_id, shares);
+        self.settle_storage(&escrow_account_id, initial_storage);
+        Unlock {
+            owner_id: &owner_id,
+            escrow_account_id: &escrow_account_id,
+            amount: amount.into(),
+        }
+        .emit();
+    }
+
+    /// Returns the tokens of `owner_id` locked by `escrow_account_id`.
+    pub fn get_locked_balance(&self, owner_id: AccountId, escrow_account_id: AccountId) -> U128 {
+        self.shares_to_amount(self.internal_locked_shares(&owner_id, &escrow_account_id))
+            .into()
+    }
+
+    /// Returns the tokens `owner_id` can transfer, the balance without the locked tokens.
+    pub fn get_unlocked_balance(&self, owner_id: AccountId) -> U128 {
+        self.internal_unlocked_balance_of(&owner_id).into()
+    }
+}
+
+impl ScaleToken {
+    pub(crate) fn internal_locked_shares(
+        &self,
+        owner_id: &AccountId,
+        escrow_account_id: &AccountId,
+    ) -> Balance {
+        self.locked_shares
+            .get(&escrow_key(owner_id, escrow_account_id))
+            .unwrap_or(0)
+    }
+
+    pub(crate) fn internal_unlocked_balance_of(&self, owner_id: &AccountId) -> Balance {
+        let account = self.get_account(owner_id);
+        self.shares_to_amount(self.unlocked_shares(owner_id, &account))
+    }
+
+    /// Returns the shares of `account` that are not locked by any escrow.
+    pub(crate) fn unlocked_shares(&self, owner_id: &AccountId, account: &Account) -> Balance {
+        let owner_hash = env::sha256(owner_id.as_bytes());
+        let locked = self.locked_totals.get(&owner_hash).unwrap_or(0);
+        account.shares.saturating_sub(locked)
+    }
+
+    /// Panics unless `owner_id` can move `shares` out of `account`.
+    pub(crate) fn assert_unlocked_shares(
+        &self,
+        owner_id: &AccountId,
+        account: &Account,
+        shares: Balance,
+    ) {
+        if account.shares < shares {
+            TokenError::NotEnoughBalance.panic();
+        }
+        if self.unlocked_shares(owner_id, account) < shares {
+            TokenError::NotEnoughUnlockedBalance.panic();
+        }
+    }
+
+    pub(crate) fn has_locked_shares(&self, owner_id: &AccountId) -> bool {
+        let owner_hash = env::sha256(owner_id.as_bytes());
+        self.locked_totals.contains_key(&owner_hash)
+    }
+
+    /// Converts `amount` of the tokens locked by `escrow_account_id` to shares. Taking
+    /// the whole locked balance takes every locked share, so no rounding dust stays locked.
+    pub(crate) fn locked_shares_of_amount(
+        &self,
+        owner_id: &AccountId,
+        escrow_account_id: &AccountId,
+        amount: Balance,
+    ) -> Balance {
+        let locked = self.internal_locked_shares(owner_id, escrow_account_id);
+        let locked_amount = self.shares_to_amount(locked);
+        if amount > locked_amount {
+            TokenError::NotEnoughLockedBalance.panic();
+        }
+        if amount == locked_amount {
+            locked
+        } else {
+            self.amount_to_shares(amount)
+        }
+    }
+
+    fn increase_locked_shares(
+        &mut self,
+        owner_id: &AccountId,
+        escrow_account_id: &AccountId,
+        shares: Balance,
+    ) {
+        let key = escrow_key(owner_id, escrow_account_id);
+        let locked = self.locked_shares.get(&key).unwrap_or(0);
+        self.locked_shares.insert(&key, &(locked + shares));
+        let owner_hash = env::sha256(owner_id.as_bytes());
+        let total = self.locked_totals.get(&owner_hash).unwrap_or(0);
+        self.locked_totals.insert(&owner_hash, &(total + shares));
+    }
+
+    /// Gives `amount` back to the allowance of `escrow_account_id`, keeping its expiry.
+    fn restore_allowance(
+        &mut self,
+        owner_id: &AccountId,
+        escrow_account_id: &AccountId,
+        amount: Balance,
+    ) {
+        let mut account = self.get_account(owner_id);
+        let allowance = self
+            .allowance_of(owner_id, &account, escrow_account_id)
+            .saturating_add(amount);
+        let allowance_storage = env::storage_usage();
+        account.set_allowance(escrow_account_id, allowance);
+        self.take_legacy_allowance(owner_id, escrow_account_id);
+        self.track_allowance_storage(owner_id, allowance_storage);
+        self.set_account(owner_id, &mut account);
+        Approve {
+            owner_id,
+            escrow_account_id,
+            amount: allowance.into(),
+            expires_at: self
+                .internal_allowance_expiry(owner_id, escrow_account_id)
+                .as_ref(),
+        }
+        .emit();
+    }
+
+    /// The expiry kept for the locks goes once the last lock and the allowance are gone.
+    pub(crate) fn decrease_locked_shares(
+        &mut self,
+        owner_id: &AccountId,
+        escrow_account_id: &AccountId,
+        shares: Balance,
+    ) {
+        let key = escrow_key(owner_id, escrow_account_id);
+        let locked = self.locked_shares.get(&key).unwrap_or(0) - shares;
+        if locked > 0 {
+            self.locked_shares.insert(&key, &locked);
+        } else {
+            self.locked_shares.remove(&key);
+            let account = self.get_account(owner_id);
+            if self.allowance_of(owner_id, &account, escrow_account_id) == 0 {
+                let allowance_storage = env::storage_usage();
+                self.set_allowance_expiry(owner_id, escrow_account_id, None);
+                self.track_allowance_storage(owner_id, allowance_storage);
+            }
+        }
+        let owner_hash = env::sha256(owner_id.as_bytes());
+        let total = self.locked_totals.get(&owner_hash).unwrap_or(0) - shares;
+        if total > 0 {
+            self.locked_totals.insert(&owner_hash, &total);
+        } else {
+            self.locked_totals.remove(&owner_hash);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use near_sdk::json_types::U128;
+use near_sdk::{
+    env, ext_contract, near_bindgen, AccountId, Balance, Gas, PromiseOrValue, PromiseResult,
+};
+
+use crate::{assert_one_yocto, ScaleToken, TokenError};
+
+const NO_DEPOSIT: Balance = 0;
+
+/// Gas reserved for `ft_resolve_transfer` callback.
+const GAS_FOR_RESOLVE_TRANSFER: Gas = 10_000_000_000_000;
+
+/// Gas kept by `ft_transfer_call` itself, the rest goes to `ft_on_transfer`.
+const GAS_FOR_FT_TRANSFER_CALL: Gas = 25_000_000_000_000 + GAS_FOR_RESOLVE_TRANSFER;
+
+#[ext_contract(ext_fungible_token_receiver)]
+pub trait FungibleTokenReceiver {
+    /// Returns the amount of unused tokens that should be returned to the sender.
+    fn ft_on_transfer(&mut self, sender_id: AccountId, amount: U128, msg: String) -> PromiseOrValue<U128>;
+}
+
+#[ext_contract(ext_self)]
+pub trait FungibleTokenResolver {
+    fn ft_resolve_transfer(&mut self, sender_id: AccountId, receiver_id: AccountId, amount: U128) -> U128;
+}
+
+/// NEP-141 Fungible Token core standard.
+/// Works on the same `accounts` as the NEP-21 methods.
+#[near_bindgen]
+impl ScaleToken {
+    /// Transfers `amount` to `receiver_id`. Requires exactly 1 yoctoNEAR attached,
+    /// storage of a new receiver is paid from the storage balance of the sender.
+    #[payable]
+    pub fn ft_transfer(&mut self, receiver_id: AccountId, amount: U128, memo: Option<String>) {
+        assert_one_yocto();
+        self.assert_not_paused();
+        let initial_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        self.internal_transfer(&sender_id, &receiver_id, amount.into(), memo.as_deref());
+        self.settle_storage(&sender_id, initial_storage);
+    }
+
+    /// Transfers `amount` to `receiver_id` and calls `ft_on_transfer` on it.
+    /// Tokens returned by the receiver are refunded in `ft_resolve_transfer`.
+    #[payable]
+    pub fn ft_transfer_call(
+        &mut self,
+        receiver_id: AccountId,
+        amount: U128,
+        memo: Option<String>,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        assert_one_yocto();
+        self.assert_not_paused();
+        if env::prepaid_gas() <= GAS_FOR_FT_TRANSFER_CALL {
+            TokenError::NotEnoughGas.panic();
+        }
+        let initial_storage = env::storage_usage();
+        let sender_id = env::predecessor_account_id();
+        let amount: Balance = amount.into();
+        self.internal_transfer(&sender_id, &receiver_id, amount, memo.as_deref());
+        self.settle_storage(&sender_id, initial_storage);
+
+        ext_fungible_token_receiver::ft_on_transfer(
+            sender_id.clone(),
+            amount.into(),
+            msg,
+            &receiver_id,
+            NO_DEPOSIT,
+            env::prepaid_gas() - GAS_FOR_FT_TRANSFER_CALL,
+        )
+        .then(ext_self::ft_resolve_transfer(
+            sender_id,
+            receiver_id,
+            amount.into(),
+            &env::current_account_id(),
+            NO_DEPOSIT,
+            GAS_FOR_RESOLVE_TRANSFER,
+        ))
+        .into()
+    }
+
+    /// Callback of `ft_transfer_call`. Returns unused tokens back to the sender
+    /// and returns the amount that was actually used by the receiver.
+    pub fn ft_resolve_transfer(
+        &mut self,
+        sender_id: AccountId,
+        receiver_id: AccountId,
+        amount: U128,
+    ) -> U128 {
+        if env::predecessor_account_id() != env::current_account_id() {
+            TokenError::PrivateMethod("ft_resolve_transfer").panic();
+        }
+        let amount: Balance = amount.into();
+
+        let unused_amount = match env::promise_result(0) {
+            PromiseResult::NotReady => unreachable!(),
+            PromiseResult::Successful(value) => {
+                if let Ok(unused_amount) = near_sdk::serde_json::from_slice::<U128>(&value) {
+                    std::cmp::min(amount, unused_amount.0)
+                } else {
+                    amount
+                }
+            }
+            PromiseResult::Failed => amount,
+        };
+
+        if unused_amount > 0 {
+            // A refund can't fail. A sender record removed meanwhile is not re-created, the
+            // tokens stay with the receiver.
+            let sender_hash = env::sha256(sender_id.as_bytes());
+            if !self.accounts.contains_key(&sender_hash) {
+                env::log(
+                    format!("The account {} is not registered, the refund is kept", sender_id)
+                        .as_bytes(),
+                );
+                return amount.into();
+            }
+            // The receiver could have spent or locked the tokens already, refund what is left.
+            let receiver_account = self.get_account(&receiver_id);
+            let receiver_shares = self.unlocked_shares(&receiver_id, &receiver_account);
+            let shares = if receiver_shares == 0 || self.total_supply == 0 {
+                0
+            } else {
+                std::cmp::min(self.amount_to_shares(unused_amount), receiver_shares)
+            };
+            // Less than a share is not refunded.
+            if shares > 0 {
+                let initial_storage = env::storage_usage();
+                let refund_amount = self.internal_refund(&receiver_id, &sender_id, shares);
+                self.settle_refund_storage(&sender_id, initial_storage);
+                return amount.saturating_sub(refund_amount).into();
+            }
+        }
+        amount.into()
+    }
+
+    /// Returns total supply of tokens.
+    pub fn ft_total_supply(&self) -> U128 {
+        self.total_supply.into()
+    }
+
+    /// Returns balance of the `account_id` account.
+    pub fn ft_balance_of(&self, account_id: AccountId) -> U128 {
+        self.internal_balance_of(&account_id).into()
+    }
+}
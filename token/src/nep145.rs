@@ -0,0 +1,414 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId, Balance, Promise, StorageUsage};
+
+use crate::events::{Event, FtBurn, StorageDeposit, StorageRefund};
+use crate::{assert_one_yocto, Account, ScaleToken, TokenError, STORAGE_PRICE_PER_BYTE};
+
+/// Prepaid storage of one account.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StorageAccount {
+    /// NEAR deposited for storage.
+    pub deposit: Balance,
+    /// Bytes of storage currently charged to the account.
+    pub usage: StorageUsage,
+}
+
+impl StorageAccount {
+    pub fn available(&self) -> Balance {
+        self.deposit
+            .saturating_sub(Balance::from(self.usage) * STORAGE_PRICE_PER_BYTE)
+    }
+}
+
+/// Account that paid for the storage of an entry of another account.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StoragePayer {
+    pub payer_id: AccountId,
+    /// Bytes of the entry charged to the payer.
+    pub bytes: StorageUsage,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    pub total: U128,
+    pub available: U128,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    pub min: U128,
+    pub max: Option<U128>,
+}
+
+/// NEP-145 Storage Management standard.
+/// Transfers and approvals are paid from the prepaid storage balance of the caller.
+#[near_bindgen]
+impl ScaleToken {
+    /// Deposits the attached amount to the storage balance of `account_id` (the caller by default).
+    /// A new account is registered if the deposit covers the minimum storage balance.
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        if !env::is_valid_account_id(account_id.as_bytes()) {
+            TokenError::InvalidAccountId.panic();
+        }
+        let account_hash = env::sha256(account_id.as_bytes());
+        if registration_only.unwrap_or(false) {
+            let refund = if self.storage_accounts.contains_key(&account_hash) {
+                amount
+            } else {
+                let min_balance = self.storage_balance_bounds().min.0;
+                if amount < min_balance {
+                    TokenError::DepositBelowMinimum(min_balance).panic();
+                }
+                self.internal_register(&account_id, min_balance);
+                amount - min_balance
+            };
+            if refund > 0 {
+                refund_storage_deposit(env::predecessor_account_id(), refund);
+            }
+        } else {
+            self.internal_storage_deposit(&account_id, amount);
+        }
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    /// Withdraws `amount` (all available by default) from the storage balance of the caller.
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let account_hash = env::sha256(account_id.as_bytes());
+        let mut storage_account = self.get_storage_account(&account_id);
+        let available = storage_account.available();
+        let amount = amount.map(|amount| amount.0).unwrap_or(available);
+        if amount > available {
+            TokenError::WithdrawExceedsAvailable.panic();
+        }
+        if amount > 0 {
+            storage_account.deposit -= amount;
+            self.storage_accounts.insert(&account_hash, &storage_account);
+            refund_storage_deposit(account_id.clone(), amount);
+        }
+        self.storage_balance_of(account_id).unwrap()
+    }
+
+    /// Unregisters the caller and returns the storage deposit that is not used by other entries.
+    /// A positive token balance is burned only if `force` is set. Fails while the caller pays
+    /// for the records of other accounts, see `get_storage_credit`.
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let account_hash = env::sha256(account_id.as_bytes());
+        let storage_account = match self.storage_accounts.get(&account_hash) {
+            Some(storage_account) => storage_account,
+            None => {
+                env::log(format!("The account {} is not registered", account_id).as_bytes());
+                return false;
+            }
+        };
+        let account = self.get_account(&account_id);
+        if account.allowances_count > 0 || self.legacy_allowances_count(&account_id) > 0 {
+            TokenError::UnregisterWithAllowances.panic();
+        }
+        if self.has_locked_shares(&account_id) {
+            TokenError::UnregisterWithLockedBalance.panic();
+        }
+        // The credit is refunded when those records are removed, it would be lost with the
+        // storage balance.
+        if self.storage_credits.contains_key(&account_hash) {
+            TokenError::UnregisterWithStorageCredit.panic();
+        }
+        if account.shares > 0 {
+            if !force.unwrap_or(false) {
+                TokenError::UnregisterWithBalance.panic();
+            }
+            let balance = self.shares_to_amount(account.shares);
+            self.total_supply -= balance;
+            self.total_shares -= account.shares;
+            self.record_account_checkpoint(account_hash.clone(), 0);
+            self.record_supply_checkpoint();
+            FtBurn {
+                owner_id: &account_id,
+                amount: balance.into(),
+                memo: Some("storage_unregister"),
+            }
+            .emit();
+        }
+
+        let initial_storage = env::storage_usage();
+        self.holders.remove(&account_id);
+        self.accounts.remove(&account_hash);
+        self.legacy_accounts.remove(&account_hash);
+        self.storage_accounts.remove(&account_hash);
+        // The account record could be paid by someone else.
+        let payer_bytes = match self.account_payers.remove(&account_hash) {
+            Some(payer) => payer.bytes + self.refund_payer(&payer.payer_id, payer.bytes),
+            None => 0,
+        };
+        let released = (initial_storage - env::storage_usage()).saturating_sub(payer_bytes);
+
+        // Storage of entries paid by this account for others stays locked.
+        let locked = Balance::from(storage_account.usage.saturating_sub(released))
+            * STORAGE_PRICE_PER_BYTE;
+        let refund = storage_account.deposit.saturating_sub(locked);
+        if refund > 0 {
+            refund_storage_deposit(account_id, refund);
+        }
+        true
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: (Balance::from(self.account_storage_usage) * STORAGE_PRICE_PER_BYTE).into(),
+            max: None,
+        }
+    }
+
+    /// Returns the storage balance locked by `account_id` for entries that are not freed yet.
+    /// It is credited back to the account when the entries are removed.
+    pub fn get_storage_credit(&self, account_id: AccountId) -> U128 {
+        let account_hash = env::sha256(account_id.as_bytes());
+        let bytes = self.storage_credits.get(&account_hash).unwrap_or(0);
+        (Balance::from(bytes) * STORAGE_PRICE_PER_BYTE).into()
+    }
+
+    /// Returns the account that paid for the account record of `account_id`, if it was
+    /// created by someone else.
+    pub fn get_storage_payer(&self, account_id: AccountId) -> Option<AccountId> {
+        let account_hash = env::sha256(account_id.as_bytes());
+        self.account_payers.get(&account_hash).map(|payer| payer.payer_id)
+    }
+
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        let account_hash = env::sha256(account_id.as_bytes());
+        self.storage_accounts
+            .get(&account_hash)
+            .map(|storage_account| StorageBalance {
+                total: storage_account.deposit.into(),
+                available: storage_account.available().into(),
+            })
+    }
+}
+
+impl ScaleToken {
+    /// Measures the storage of a registered account, used as the minimum storage balance.
+    pub(crate) fn measure_account_storage_usage(&mut self) {
+        let initial_storage = env::storage_usage();
+        let tmp_account_id = "a".repeat(64);
+        let tmp_hash = env::sha256(tmp_account_id.as_bytes());
+        self.storage_accounts
+            .insert(&tmp_hash, &StorageAccount { deposit: 0, usage: 0 });
+        self.accounts
+            .insert(&tmp_hash, &Account::new(tmp_hash.clone()));
+        self.account_storage_usage = env::storage_usage() - initial_storage;
+        self.storage_accounts.remove(&tmp_hash);
+        self.accounts.remove(&tmp_hash);
+    }
+
+    pub(crate) fn get_storage_account(&self, account_id: &AccountId) -> StorageAccount {
+        let account_hash = env::sha256(account_id.as_bytes());
+        self.storage_accounts.get(&account_hash).unwrap_or_else(|| {
+            TokenError::NotRegistered(account_id.clone()).panic()
+        })
+    }
+
+    /// Adds `amount` to the storage balance of `account_id`, registering it if needed.
+    pub(crate) fn internal_storage_deposit(&mut self, account_id: &AccountId, amount: Balance) {
+        let account_hash = env::sha256(account_id.as_bytes());
+        match self.storage_accounts.get(&account_hash) {
+            Some(mut storage_account) => {
+                storage_account.deposit += amount;
+                self.storage_accounts.insert(&account_hash, &storage_account);
+                StorageDeposit {
+                    account_id,
+                    amount: amount.into(),
+                }
+                .emit();
+            }
+            None => self.internal_register(account_id, amount),
+        }
+    }
+
+    /// Credits the attached deposit of a payable call to the storage balance of the caller.
+    pub(crate) fn deposit_attached_storage(&mut self) {
+        let amount = env::attached_deposit();
+        if amount > 0 {
+            self.internal_storage_deposit(&env::predecessor_account_id(), amount);
+        }
+    }
+
+    fn internal_register(&mut self, account_id: &AccountId, deposit: Balance) {
+        let min_balance = self.storage_balance_bounds().min.0;
+        if deposit < min_balance {
+            TokenError::DepositBelowMinimum(min_balance).panic();
+        }
+        let initial_storage = env::storage_usage();
+        let account_hash = env::sha256(account_id.as_bytes());
+        self.storage_accounts
+            .insert(&account_hash, &StorageAccount { deposit, usage: 0 });
+        if !self.accounts.contains_key(&account_hash)
+            && !self.legacy_accounts.contains_key(&account_hash)
+        {
+            self.accounts
+                .insert(&account_hash, &Account::new(account_hash.clone()));
+        }
+        let usage = env::storage_usage() - initial_storage;
+        self.storage_accounts
+            .insert(&account_hash, &StorageAccount { deposit, usage });
+        StorageDeposit {
+            account_id,
+            amount: deposit.into(),
+        }
+        .emit();
+    }
+
+    /// Charges the storage used since `initial_storage` to the storage balance of `payer_id`,
+    /// or credits it back if storage was released.
+    pub(crate) fn settle_storage(&mut self, payer_id: &AccountId, initial_storage: StorageUsage) {
+        // Storage released to the payers of removed entries is credited to them already.
+        let current_storage = env::storage_usage() + std::mem::take(&mut self.released_storage);
+        if current_storage == initial_storage {
+            return;
+        }
+        let payer_hash = env::sha256(payer_id.as_bytes());
+        if current_storage > initial_storage {
+            let mut storage_account = self.get_storage_account(payer_id);
+            storage_account.usage += current_storage - initial_storage;
+            let required = Balance::from(storage_account.usage) * STORAGE_PRICE_PER_BYTE;
+            if required > storage_account.deposit {
+                TokenError::InsufficientStorageBalance {
+                    required,
+                    balance: storage_account.deposit,
+                }
+                .panic();
+            }
+            self.storage_accounts.insert(&payer_hash, &storage_account);
+        } else if let Some(mut storage_account) = self.storage_accounts.get(&payer_hash) {
+            storage_account.usage = storage_account
+                .usage
+                .saturating_sub(initial_storage - current_storage);
+            self.storage_accounts.insert(&payer_hash, &storage_account);
+        }
+    }
+
+    /// Settles the storage changed by a refund to `sender_id` since `initial_storage`. A refund
+    /// can't be rejected once the receiver returned the tokens, so the holder entry of the
+    /// sender is charged even beyond its storage balance. The sender tops it up before its
+    /// next growth.
+    pub(crate) fn settle_refund_storage(
+        &mut self,
+        sender_id: &AccountId,
+        initial_storage: StorageUsage,
+    ) {
+        let current_storage = env::storage_usage() + std::mem::take(&mut self.released_storage);
+        let sender_hash = env::sha256(sender_id.as_bytes());
+        if let Some(mut storage_account) = self.storage_accounts.get(&sender_hash) {
+            storage_account.usage =
+                (storage_account.usage + current_storage).saturating_sub(initial_storage);
+            self.storage_accounts.insert(&sender_hash, &storage_account);
+        }
+    }
+
+    /// Tracks the account record of `account_id` changed since `initial_storage`. The record
+    /// has a fixed size, it only grows when it is created and only shrinks when it is removed.
+    /// A record created by someone else than its owner is recorded as paid by the caller and
+    /// credited back to it on removal. The owner settles its own record.
+    pub(crate) fn track_account_storage(
+        &mut self,
+        account_id: &AccountId,
+        initial_storage: StorageUsage,
+    ) {
+        let account_hash = env::sha256(account_id.as_bytes());
+        let current_storage = env::storage_usage();
+        if current_storage > initial_storage {
+            let payer_id = env::predecessor_account_id();
+            if payer_id == *account_id || self.account_payers.contains_key(&account_hash) {
+                return;
+            }
+            let mut payer = StoragePayer {
+                payer_id,
+                bytes: 0,
+            };
+            self.account_payers.insert(&account_hash, &payer);
+            payer.bytes = env::storage_usage() - initial_storage;
+            self.account_payers.insert(&account_hash, &payer);
+            self.increase_storage_credit(&payer.payer_id, payer.bytes);
+        } else if current_storage < initial_storage {
+            // The payer entry goes away together with the account record.
+            if let Some(payer) = self.account_payers.remove(&account_hash) {
+                self.release_storage(&payer.payer_id, payer.bytes);
+            }
+        }
+    }
+
+    /// Tracks allowance entries of `owner_id` changed since `initial_storage`.
+    /// Allowances are always paid by their owner.
+    pub(crate) fn track_allowance_storage(
+        &mut self,
+        owner_id: &AccountId,
+        initial_storage: StorageUsage,
+    ) {
+        let current_storage = env::storage_usage();
+        if current_storage > initial_storage {
+            self.increase_storage_credit(owner_id, current_storage - initial_storage);
+        } else if current_storage < initial_storage {
+            self.release_storage(owner_id, initial_storage - current_storage);
+        }
+    }
+
+    /// Credits `bytes` of a removed entry back to `payer_id`, excluding them from the
+    /// settlement of the caller.
+    fn release_storage(&mut self, payer_id: &AccountId, bytes: StorageUsage) {
+        let freed = self.refund_payer(payer_id, bytes);
+        self.released_storage += bytes + freed;
+    }
+
+    /// Credits `bytes` back to the storage balance of `payer_id`. The credit entry of the
+    /// payer is removed once it is settled, returns the bytes freed by removing it.
+    fn refund_payer(&mut self, payer_id: &AccountId, bytes: StorageUsage) -> StorageUsage {
+        let payer_hash = env::sha256(payer_id.as_bytes());
+        let credit = self
+            .storage_credits
+            .get(&payer_hash)
+            .unwrap_or(0)
+            .saturating_sub(bytes);
+        let initial_storage = env::storage_usage();
+        if credit > 0 {
+            self.storage_credits.insert(&payer_hash, &credit);
+        } else {
+            self.storage_credits.remove(&payer_hash);
+        }
+        let freed = initial_storage.saturating_sub(env::storage_usage());
+        if let Some(mut storage_account) = self.storage_accounts.get(&payer_hash) {
+            storage_account.usage = storage_account.usage.saturating_sub(bytes + freed);
+            self.storage_accounts.insert(&payer_hash, &storage_account);
+        }
+        freed
+    }
+
+    fn increase_storage_credit(&mut self, payer_id: &AccountId, bytes: StorageUsage) {
+        let payer_hash = env::sha256(payer_id.as_bytes());
+        let credit = self.storage_credits.get(&payer_hash).unwrap_or(0);
+        self.storage_credits.insert(&payer_hash, &(credit + bytes));
+    }
+}
+
+fn refund_storage_deposit(account_id: AccountId, amount: Balance) {
+    StorageRefund {
+        account_id: &account_id,
+        amount: amount.into(),
+    }
+    .emit();
+    Promise::new(account_id).transfer(amount);
+}
@@ -0,0 +1,65 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen};
+
+use crate::events::{Event, MetadataUpdate};
+use crate::{Role, ScaleToken, TokenError};
+
+pub const FT_METADATA_SPEC: &str = "ft-1.0.0";
+
+/// NEP-148 Fungible Token metadata.
+#[derive(BorshDeserialize, BorshSerialize, Clone, Deserialize, Serialize, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FungibleTokenMetadata {
+    pub spec: String,
+    pub name: String,
+    pub symbol: String,
+    pub icon: Option<String>,
+    pub reference: Option<String>,
+    pub reference_hash: Option<Base64VecU8>,
+    pub decimals: u8,
+}
+
+impl FungibleTokenMetadata {
+    pub fn assert_valid(&self) {
+        if self.spec != FT_METADATA_SPEC {
+            TokenError::UnsupportedMetadataSpec.panic();
+        }
+        if self.name.is_empty() {
+            TokenError::EmptyMetadataName.panic();
+        }
+        if self.symbol.is_empty() {
+            TokenError::EmptyMetadataSymbol.panic();
+        }
+        if self.reference.is_some() != self.reference_hash.is_some() {
+            TokenError::MetadataReferenceMismatch.panic();
+        }
+        if let Some(reference_hash) = &self.reference_hash {
+            if reference_hash.0.len() != 32 {
+                TokenError::InvalidMetadataReferenceHash.panic();
+            }
+        }
+    }
+}
+
+#[near_bindgen]
+impl ScaleToken {
+    pub fn ft_metadata(&self) -> FungibleTokenMetadata {
+        self.metadata.clone()
+    }
+
+    /// Replaces the metadata. Only a metadata manager can update it, `decimals` can't be changed.
+    pub fn set_ft_metadata(&mut self, metadata: FungibleTokenMetadata) {
+        self.assert_role(Role::MetadataManager);
+        metadata.assert_valid();
+        if metadata.decimals != self.metadata.decimals {
+            TokenError::DecimalsChanged.panic();
+        }
+        self.metadata = metadata;
+        MetadataUpdate {
+            updated_by: &env::predecessor_account_id(),
+        }
+        .emit();
+    }
+}
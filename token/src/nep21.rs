@@ -9,5 +9,10 @@ pub trait NEP21 {
     #[payable]
     fn transfer_from(&mut self, from: AccountId, dest: AccountId, amount: U128);
 
-    fn mintTo(&mut self, amount: u128, target: AccountId) -> U128
+    #[payable]
+    fn mint(&mut self, account_id: AccountId, amount: U128) -> U128;
+
+    fn burn_from(&mut self, account_id: AccountId, amount: U128) -> U128;
+
+    fn sync_pooled_near(&mut self, total_pooled_near: U128);
 }
\ No newline at end of file
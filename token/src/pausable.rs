@@ -0,0 +1,47 @@
+use near_sdk::{env, near_bindgen};
+
+use crate::events::{Event, Pause, Unpause};
+use crate::{Role, ScaleToken, TokenError};
+
+/// Emergency stop. While paused, tokens can't be transferred, approved or minted.
+/// Views, burning and lowering allowances keep working.
+#[near_bindgen]
+impl ScaleToken {
+    /// Pauses the token. Only a pauser (guardian) can pause.
+    pub fn pause(&mut self) {
+        self.assert_role(Role::Pauser);
+        if self.paused {
+            TokenError::AlreadyPaused.panic();
+        }
+        self.paused = true;
+        Pause {
+            account_id: &env::predecessor_account_id(),
+        }
+        .emit();
+    }
+
+    /// Unpauses the token. Only an admin can unpause.
+    pub fn unpause(&mut self) {
+        self.assert_role(Role::Admin);
+        if !self.paused {
+            TokenError::NotPaused.panic();
+        }
+        self.paused = false;
+        Unpause {
+            account_id: &env::predecessor_account_id(),
+        }
+        .emit();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+impl ScaleToken {
+    pub(crate) fn assert_not_paused(&self) {
+        if self.paused {
+            TokenError::Paused.panic();
+        }
+    }
+}
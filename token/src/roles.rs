@@ -0,0 +1,126 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedSet;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::events::{Event, RoleGranted, RoleRevoked};
+use crate::{ScaleToken, StorageKey, TokenError};
+
+/// Privileged roles of the ScaleToken.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde", rename_all = "snake_case")]
+pub enum Role {
+    /// Grants and revokes roles.
+    Admin,
+    /// Mints tokens and syncs the total pooled NEAR.
+    Minter,
+    /// Burns tokens.
+    Burner,
+    /// Pauses the token.
+    Pauser,
+    /// Updates the NEP-148 metadata.
+    MetadataManager,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::Minter => "minter",
+            Role::Burner => "burner",
+            Role::Pauser => "pauser",
+            Role::MetadataManager => "metadata_manager",
+        }
+    }
+}
+
+#[near_bindgen]
+impl ScaleToken {
+    /// Grants `role` to `account_id`. Only an admin can grant roles.
+    /// Storage is paid from the prepaid storage balance of the admin.
+    #[payable]
+    pub fn grant_role(&mut self, role: Role, account_id: AccountId) -> bool {
+        self.assert_role(Role::Admin);
+        if !env::is_valid_account_id(account_id.as_bytes()) {
+            TokenError::InvalidAccountId.panic();
+        }
+        self.deposit_attached_storage();
+        let initial_storage = env::storage_usage();
+        let granted = self.internal_grant_role(role, &account_id);
+        self.settle_storage(&env::predecessor_account_id(), initial_storage);
+        granted
+    }
+
+    /// Revokes `role` from `account_id`. Only an admin can revoke roles.
+    pub fn revoke_role(&mut self, role: Role, account_id: AccountId) -> bool {
+        self.assert_role(Role::Admin);
+        let initial_storage = env::storage_usage();
+        let revoked = self.internal_revoke_role(role, &account_id);
+        self.settle_storage(&env::predecessor_account_id(), initial_storage);
+        revoked
+    }
+
+    /// Removes `role` from the caller.
+    pub fn renounce_role(&mut self, role: Role) -> bool {
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let renounced = self.internal_revoke_role(role, &account_id);
+        self.settle_storage(&account_id, initial_storage);
+        renounced
+    }
+
+    pub fn has_role(&self, role: Role, account_id: AccountId) -> bool {
+        self.role_members(role).contains(&account_id)
+    }
+
+    pub fn get_role_members(&self, role: Role) -> Vec<AccountId> {
+        self.role_members(role).to_vec()
+    }
+}
+
+impl ScaleToken {
+    pub(crate) fn assert_role(&self, role: Role) {
+        if !self.has_role(role, env::predecessor_account_id()) {
+            TokenError::MissingRole(role).panic();
+        }
+    }
+
+    pub(crate) fn internal_grant_role(&mut self, role: Role, account_id: &AccountId) -> bool {
+        let mut members = self.role_members(role);
+        if !members.insert(account_id) {
+            return false;
+        }
+        self.roles.insert(&role, &members);
+        RoleGranted {
+            role: role.as_str(),
+            account_id,
+            sender_id: &env::predecessor_account_id(),
+        }
+        .emit();
+        true
+    }
+
+    pub(crate) fn internal_revoke_role(&mut self, role: Role, account_id: &AccountId) -> bool {
+        let mut members = self.role_members(role);
+        if role == Role::Admin && members.len() == 1 && members.contains(account_id) {
+            TokenError::LastAdmin.panic();
+        }
+        if !members.remove(account_id) {
+            return false;
+        }
+        self.roles.insert(&role, &members);
+        RoleRevoked {
+            role: role.as_str(),
+            account_id,
+            sender_id: &env::predecessor_account_id(),
+        }
+        .emit();
+        true
+    }
+
+    fn role_members(&self, role: Role) -> UnorderedSet<AccountId> {
+        self.roles
+            .get(&role)
+            .unwrap_or_else(|| UnorderedSet::new(StorageKey::RoleMembers { role }.into_prefix()))
+    }
+}
@@ -0,0 +1,73 @@
+use near_sdk::{env, near_bindgen, AccountId};
+
+use crate::events::{Event, TokenizerAccepted, TokenizerProposalCancelled, TokenizerProposed};
+use crate::{Role, ScaleToken, TokenError};
+
+/// Two-step rotation of the Tokenizer. The admin proposes a new Tokenizer,
+/// the new account accepts and takes over the minter and burner roles.
+#[near_bindgen]
+impl ScaleToken {
+    pub fn get_tokenizer(&self) -> AccountId {
+        self.tokenizer.clone()
+    }
+
+    pub fn get_pending_tokenizer(&self) -> Option<AccountId> {
+        self.pending_tokenizer.clone()
+    }
+
+    /// Proposes `new_tokenizer_id` as the Tokenizer, replacing a previous proposal.
+    /// Only an admin can propose.
+    pub fn propose_tokenizer(&mut self, new_tokenizer_id: AccountId) {
+        self.assert_role(Role::Admin);
+        if !env::is_valid_account_id(new_tokenizer_id.as_bytes()) {
+            TokenError::InvalidTokenizerAccountId.panic();
+        }
+        if new_tokenizer_id == self.tokenizer {
+            TokenError::SameTokenizer.panic();
+        }
+        TokenizerProposed {
+            old_tokenizer_id: &self.tokenizer,
+            new_tokenizer_id: &new_tokenizer_id,
+        }
+        .emit();
+        self.pending_tokenizer = Some(new_tokenizer_id);
+    }
+
+    /// Cancels the pending proposal. Only an admin can cancel.
+    pub fn cancel_tokenizer_proposal(&mut self) {
+        self.assert_role(Role::Admin);
+        let new_tokenizer_id = self
+            .pending_tokenizer
+            .take()
+            .unwrap_or_else(|| TokenError::NoPendingTokenizer.panic());
+        TokenizerProposalCancelled {
+            new_tokenizer_id: &new_tokenizer_id,
+        }
+        .emit();
+    }
+
+    /// Called by the proposed account to become the Tokenizer.
+    /// Storage of the new role entries is paid from the storage balance of the new Tokenizer.
+    #[payable]
+    pub fn accept_tokenizer(&mut self) {
+        let new_tokenizer_id = env::predecessor_account_id();
+        if self.pending_tokenizer.as_ref() != Some(&new_tokenizer_id) {
+            TokenError::NotPendingTokenizer.panic();
+        }
+        self.deposit_attached_storage();
+        let initial_storage = env::storage_usage();
+        let old_tokenizer_id =
+            std::mem::replace(&mut self.tokenizer, new_tokenizer_id.clone());
+        self.pending_tokenizer = None;
+        for role in [Role::Minter, Role::Burner].iter() {
+            self.internal_revoke_role(*role, &old_tokenizer_id);
+            self.internal_grant_role(*role, &new_tokenizer_id);
+        }
+        self.settle_storage(&new_tokenizer_id, initial_storage);
+        TokenizerAccepted {
+            old_tokenizer_id: &old_tokenizer_id,
+            new_tokenizer_id: &new_tokenizer_id,
+        }
+        .emit();
+    }
+}
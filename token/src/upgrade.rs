@@ -0,0 +1,202 @@
+//! State versioning and self-upgrade.
+//!
+//! The layout version is kept under its own storage key, so the contract state stays a
+//! plain struct. A missing key means version 1, the layout deployed before versioning.
+//! Layouts of deployed versions are frozen here and converted by `migrate`.
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LookupMap, UnorderedSet, Vector};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::{env, near_bindgen, AccountId, Balance, Gas, Promise};
+
+use crate::{
+    Account, FungibleTokenMetadata, Role, ScaleToken, StorageKey, TokenError, FT_METADATA_SPEC,
+};
+
+const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+
+/// Version of the current `ScaleToken` layout.
+pub const STATE_VERSION: u32 = 2;
+
+/// Gas attached to the `migrate` call of `upgrade`.
+const GAS_FOR_MIGRATE: Gas = 100_000_000_000_000;
+
+/// Account record of version 1, with a balance instead of shares.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct AccountV1 {
+    pub balance: Balance,
+    /// sha256(escrow AccountID) -> Allowance, under the raw sha256(AccountID) prefix.
+    pub allowances: LookupMap<Vec<u8>, Balance>,
+    pub allowances_count: u32,
+}
+
+impl AccountV1 {
+    /// Converts the record to an account without allowances. Balances of v1 are shares 1:1,
+    /// allowances stay under the v1 keys until they are moved.
+    pub(crate) fn to_account(&self, account_hash: Vec<u8>) -> Account {
+        let mut account = Account::new(account_hash);
+        account.shares = self.balance;
+        account
+    }
+}
+
+/// `ScaleToken` layout version 1, deployed before versioning.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ScaleTokenV1 {
+    /// sha256(AccountID) -> Account details, under the prefix b"a".
+    pub accounts: LookupMap<Vec<u8>, AccountV1>,
+    pub total_supply: Balance,
+    pub decimals: u8,
+    pub tokenizer: AccountId,
+}
+
+impl From<ScaleTokenV1> for ScaleToken {
+    fn from(state: ScaleTokenV1) -> Self {
+        // v1 accounts stay under b"a" and are moved one by one, see `set_account`. Every
+        // token is one share, holders join the index as they are moved.
+        let mut roles = LookupMap::new(StorageKey::Roles.into_prefix());
+        grant_role(&mut roles, Role::Minter, &state.tokenizer);
+        grant_role(&mut roles, Role::Burner, &state.tokenizer);
+        // v1 has no admin, the account that deploys the upgrade becomes one.
+        grant_role(&mut roles, Role::Admin, &env::current_account_id());
+        grant_role(&mut roles, Role::MetadataManager, &env::current_account_id());
+        Self {
+            accounts: LookupMap::new(StorageKey::Accounts.into_prefix()),
+            legacy_accounts: state.accounts,
+            holders: UnorderedSet::new(StorageKey::Holders.into_prefix()),
+            total_supply: state.total_supply,
+            total_shares: state.total_supply,
+            account_checkpoints: LookupMap::new(StorageKey::AccountCheckpoints.into_prefix()),
+            supply_checkpoints: Vector::new(StorageKey::SupplyCheckpoints.into_prefix()),
+            metadata: FungibleTokenMetadata {
+                spec: FT_METADATA_SPEC.to_string(),
+                name: "Scale Token".to_string(),
+                symbol: "SCALE".to_string(),
+                icon: None,
+                reference: None,
+                reference_hash: None,
+                decimals: state.decimals,
+            },
+            tokenizer: state.tokenizer,
+            pending_tokenizer: None,
+            roles,
+            paused: false,
+            storage_accounts: LookupMap::new(StorageKey::StorageAccounts.into_prefix()),
+            account_storage_usage: 0,
+            account_payers: LookupMap::new(StorageKey::AccountPayers.into_prefix()),
+            storage_credits: LookupMap::new(StorageKey::StorageCredits.into_prefix()),
+            allowance_expiries: LookupMap::new(StorageKey::AllowanceExpiries.into_prefix()),
+            locked_shares: LookupMap::new(StorageKey::LockedShares.into_prefix()),
+            locked_totals: LookupMap::new(StorageKey::LockedTotals.into_prefix()),
+            released_storage: 0,
+        }
+    }
+}
+
+/// State of any deployed version.
+pub enum VersionedScaleToken {
+    V1(ScaleTokenV1),
+    Current(ScaleToken),
+}
+
+impl VersionedScaleToken {
+    fn read() -> Self {
+        match read_state_version() {
+            1 => VersionedScaleToken::V1(
+                env::state_read().unwrap_or_else(|| TokenError::NotInitialized.panic()),
+            ),
+            STATE_VERSION => VersionedScaleToken::Current(
+                env::state_read().unwrap_or_else(|| TokenError::NotInitialized.panic()),
+            ),
+            version => TokenError::UnsupportedStateVersion(version).panic(),
+        }
+    }
+}
+
+impl From<VersionedScaleToken> for ScaleToken {
+    fn from(state: VersionedScaleToken) -> Self {
+        match state {
+            VersionedScaleToken::V1(state) => state.into(),
+            VersionedScaleToken::Current(state) => state,
+        }
+    }
+}
+
+#[near_bindgen]
+impl ScaleToken {
+    /// Converts the state written by any deployed version to the current layout.
+    /// Called by `upgrade` right after the new code is deployed.
+    #[init]
+    pub fn migrate() -> Self {
+        if env::predecessor_account_id() != env::current_account_id() {
+            TokenError::PrivateMethod("migrate").panic();
+        }
+        let mut state: ScaleToken = VersionedScaleToken::read().into();
+        state.measure_account_storage_usage();
+        write_state_version();
+        state
+    }
+
+    /// Moves the given v1 accounts to `accounts`, together with their allowances of the
+    /// listed escrows. Allowances of v1 are keyed by the escrow hash and can't be listed, the
+    /// escrows come from the `approve` calls made to v1. Allowances of unlisted escrows stay
+    /// readable and move on their next update. Only an admin can migrate, storage is paid
+    /// from the storage balance of the admin. Returns the number of migrated accounts.
+    #[payable]
+    pub fn migrate_accounts(&mut self, accounts: Vec<(AccountId, Vec<AccountId>)>) -> u32 {
+        self.assert_role(Role::Admin);
+        self.deposit_attached_storage();
+        let initial_storage = env::storage_usage();
+        let mut migrated = 0;
+        for (account_id, escrow_account_ids) in accounts.iter() {
+            let account_hash = env::sha256(account_id.as_bytes());
+            if !self.legacy_accounts.contains_key(&account_hash) {
+                continue;
+            }
+            let mut account = self.get_account(account_id);
+            for escrow_account_id in escrow_account_ids.iter() {
+                if let Some(allowance) = self.take_legacy_allowance(account_id, escrow_account_id)
+                {
+                    account.set_allowance(escrow_account_id, allowance);
+                }
+            }
+            self.set_account(account_id, &mut account);
+            migrated += 1;
+        }
+        self.settle_storage(&env::predecessor_account_id(), initial_storage);
+        migrated
+    }
+
+    /// Deploys `code` to this account and calls `migrate` on it. Only an admin can upgrade.
+    pub fn upgrade(&mut self, code: Base64VecU8) -> Promise {
+        self.assert_role(Role::Admin);
+        Promise::new(env::current_account_id())
+            .deploy_contract(code.0)
+            .function_call(b"migrate".to_vec(), vec![], 0, GAS_FOR_MIGRATE)
+    }
+
+    pub fn get_state_version(&self) -> u32 {
+        read_state_version()
+    }
+}
+
+fn grant_role(
+    roles: &mut LookupMap<Role, UnorderedSet<AccountId>>,
+    role: Role,
+    account_id: &AccountId,
+) {
+    let mut members = UnorderedSet::new(StorageKey::RoleMembers { role }.into_prefix());
+    members.insert(account_id);
+    roles.insert(&role, &members);
+}
+
+pub(crate) fn read_state_version() -> u32 {
+    env::storage_read(STATE_VERSION_KEY)
+        .map(|value| {
+            u32::try_from_slice(&value).unwrap_or_else(|_| TokenError::InvalidStateVersion.panic())
+        })
+        .unwrap_or(1)
+}
+
+pub(crate) fn write_state_version() {
+    env::storage_write(STATE_VERSION_KEY, &STATE_VERSION.try_to_vec().unwrap());
+}
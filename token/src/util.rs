@@ -1,2 +1,30 @@
+use uint::construct_uint;
+
 // Near supported 1e24
-pub const DECIMAL: u128 = 1_000_000_000_000_000_000_000_000;
\ No newline at end of file
+pub const DECIMAL: u128 = 1_000_000_000_000_000_000_000_000;
+
+construct_uint! {
+    /// 256-bit unsigned integer.
+    pub struct U256(4);
+}
+
+/// Returns `a * b / c` rounded down, multiplied in 256 bits. `None` if the result doesn't fit
+/// in u128.
+pub fn mul_div(a: u128, b: u128, c: u128) -> Option<u128> {
+    to_u128(U256::from(a) * U256::from(b) / U256::from(c))
+}
+
+/// Returns `a * b / c` rounded up, multiplied in 256 bits. `None` if the result doesn't fit
+/// in u128.
+pub fn mul_div_ceil(a: u128, b: u128, c: u128) -> Option<u128> {
+    let c = U256::from(c);
+    to_u128((U256::from(a) * U256::from(b) + c - 1) / c)
+}
+
+fn to_u128(value: U256) -> Option<u128> {
+    if value > U256::from(u128::MAX) {
+        None
+    } else {
+        Some(value.as_u128())
+    }
+}
@@ -5,7 +5,18 @@ use near_sdk::{
     env, near_bindgen, AccountId, Balance, Promise, StorageUsage
 };
 
-const TOKENIZER: &[u8] = b"TOKENIZER";
+/// Prefixes of all persistent collections. A prefix starts with the variant index, so key
+/// spaces of different collections never overlap. New variants must be appended at the end.
+#[derive(BorshSerialize)]
+pub(crate) enum StorageKey {
+    Registry,
+}
+
+impl StorageKey {
+    pub(crate) fn into_prefix(self) -> Vec<u8> {
+        self.try_to_vec().unwrap()
+    }
+}
 const STORAGE_PRICE_PER_BYTE: Balance = 100_000_000_000_000_000_000;
 
 #[near_bindgen]
@@ -17,10 +28,9 @@ pub struct Tokenizer {
 
 impl Tokenizer {
     pub fn new(governor: AccountId) -> Self {
-        let index_key_id = append(&TOKENIZER, b'l');
         Self {
             governance: governor,
-            registry: Vector::new(index_key_id)
+            registry: Vector::new(StorageKey::Registry.into_prefix())
         }
     }
 